@@ -0,0 +1,43 @@
+use serenity::all::GuildId;
+use songbird::tracks::TrackHandle;
+use std::collections::HashMap;
+
+/// A looping background sound which plays independently of the queue
+pub struct AmbienceTrack {
+    pub name: String,
+    pub volume: f32,
+    handle: TrackHandle,
+}
+
+/// The ambience layers of all guilds
+#[derive(Default)]
+pub struct Ambience(HashMap<GuildId, Vec<AmbienceTrack>>);
+
+impl Ambience {
+    /// Keep the given track looping as part of the ambience of the guild
+    pub fn add(&mut self, guild_id: GuildId, name: String, volume: f32, handle: TrackHandle) {
+        let _ = handle.set_volume(volume);
+
+        if let Err(e) = handle.enable_loop() {
+            println!("Could not loop ambience track {name}: {e:?}");
+        }
+
+        self.0.entry(guild_id).or_default().push(AmbienceTrack { name, volume, handle });
+    }
+
+    /// Stop all ambience tracks of the guild and return how many were playing
+    pub fn clear(&mut self, guild_id: GuildId) -> usize {
+        let tracks = self.0.remove(&guild_id).unwrap_or_default();
+
+        for track in &tracks {
+            let _ = track.handle.stop();
+        }
+
+        tracks.len()
+    }
+
+    /// Get the ambience tracks currently playing in the guild
+    pub fn tracks(&self, guild_id: GuildId) -> &[AmbienceTrack] {
+        self.0.get(&guild_id).map(Vec::as_slice).unwrap_or_default()
+    }
+}
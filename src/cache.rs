@@ -0,0 +1,120 @@
+use sha1::{Digest, Sha1};
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use crate::database::{CachedFile, Database, Song};
+use crate::{storage, ytdlp};
+
+/// Environment variable with the maximum size of the audio cache in megabytes
+pub const CACHE_LIMIT_VARIABLE: &str = "DMBOT_CACHE_LIMIT_MB";
+
+/// Maximum size of the audio cache in megabytes if none is configured
+const DEFAULT_CACHE_LIMIT_MB: u64 = 1024;
+
+/// Directory next to the executable where downloaded songs are stored
+const CACHE_DIRECTORY: &str = "cache";
+
+/// Get the maximum size of the audio cache in bytes
+pub fn get_cache_limit() -> u64 {
+    let megabytes = std::env::var(CACHE_LIMIT_VARIABLE)
+        .ok()
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(DEFAULT_CACHE_LIMIT_MB);
+
+    megabytes * 1024 * 1024
+}
+
+/// Download the audio of a registered YouTube song into the cache
+pub async fn download(song: &Song) -> Result<CachedFile, String> {
+    if song.video_id.is_none() {
+        return Err("Only YouTube songs can be cached".to_string())
+    }
+
+    let url = song.location();
+    // named after the song instead of the video, as a video can be registered in a guild and globally,
+    // and evicting one of them must not remove the file of the other
+    let file_name = format!("{}.m4a", song.id);
+
+    tokio::task::spawn_blocking(move || {
+        let mut path = storage::get_directory(CACHE_DIRECTORY)?;
+        path.push(file_name);
+
+        ytdlp::download_audio(&url, &path)?;
+
+        let size = std::fs::metadata(&path).map_err(|e| format!("Could not read downloaded file: {e}"))?.len();
+
+        Ok(CachedFile {
+            checksum: checksum(&path)?,
+            path: path.to_string_lossy().to_string(),
+            size,
+        })
+    }).await.map_err(|e| format!("{e:?}"))?
+}
+
+/// Check that the cached file still exists with the size it was downloaded with.
+/// This is cheap enough to be done whenever the song is played, unlike [`is_intact`].
+pub fn is_valid(cached_file: &CachedFile) -> bool {
+    match std::fs::metadata(&cached_file.path) {
+        Ok(metadata) => metadata.len() == cached_file.size,
+        Err(_) => false
+    }
+}
+
+/// Check that the content of the cached file was not altered since it was downloaded
+pub async fn is_intact(cached_file: &CachedFile) -> bool {
+    let path = PathBuf::from(&cached_file.path);
+
+    match tokio::task::spawn_blocking(move || checksum(&path)).await {
+        Ok(Ok(checksum)) => checksum == cached_file.checksum,
+        _ => false
+    }
+}
+
+/// Delete the cached file of a song and forget about it
+pub fn remove(database: &Database, song_id: i64, cached_file: &CachedFile) -> Result<(), String> {
+    let _ = std::fs::remove_file(&cached_file.path);
+    database.set_cached_file(song_id, None)
+}
+
+/// Remove the least recently used songs from the cache until it fits into the size limit.
+/// The songs with the given ids are kept in any case. Returns the amount of removed songs.
+pub fn evict(database: &Database, keep_song_ids: &[i64]) -> Result<usize, String> {
+    let cached_songs = database.get_cached_songs()?;
+    let mut total_size = cached_songs.iter().map(|(_, cached_file)| cached_file.size).sum::<u64>();
+    let limit = get_cache_limit();
+    let mut evicted = 0;
+
+    for (song_id, cached_file) in cached_songs {
+        if total_size <= limit {
+            break
+        }
+
+        if keep_song_ids.contains(&song_id) {
+            continue
+        }
+
+        remove(database, song_id, &cached_file)?;
+        total_size -= cached_file.size;
+        evicted += 1;
+    }
+
+    Ok(evicted)
+}
+
+fn checksum(path: &PathBuf) -> Result<String, String> {
+    let mut file = File::open(path).map_err(|e| format!("Could not open cached file: {e}"))?;
+    let mut hasher = Sha1::new();
+    let mut buffer = [0; 64 * 1024];
+
+    loop {
+        let read = file.read(&mut buffer).map_err(|e| format!("Could not read cached file: {e}"))?;
+
+        if read == 0 {
+            break
+        }
+
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
@@ -1,18 +1,335 @@
-use rusqlite::{Connection, Error};
-use std::path::PathBuf;
+use rusqlite::{Connection, Error, OptionalExtension, Row};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use itertools::Itertools;
+use crate::library::LocalFile;
+use crate::search;
+
+/// All schema migrations in the order they have to be applied. The version of a migration
+/// is its position in this list, starting at 1. Never change or reorder an existing entry,
+/// only append new ones, as already applied versions are skipped when the bot starts.
+const MIGRATIONS: &[&str] = &[
+    // 1: initial table of registered songs
+    "\
+    CREATE TABLE IF NOT EXISTS Songs (
+        video_id TEXT PRIMARY KEY,
+        video_title TEXT
+    );
+    ",
+    // 2: scope songs by guild, existing songs are moved to the global library
+    "\
+    CREATE TABLE GuildSongs (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        guild_id INTEGER NOT NULL,
+        video_id TEXT NOT NULL,
+        video_title TEXT,
+        UNIQUE (guild_id, video_id)
+    );
+    INSERT INTO GuildSongs (guild_id, video_id, video_title) SELECT 0, video_id, video_title FROM Songs;
+    DROP TABLE Songs;
+    ALTER TABLE GuildSongs RENAME TO Songs;
+    ",
+    // 3: tags like "combat" or "tavern" attached to songs. They belong to the guild which set them,
+    // so guilds sharing global songs do not see each others tags.
+    "\
+    CREATE TABLE SongTags (
+        guild_id INTEGER NOT NULL,
+        song_id INTEGER NOT NULL REFERENCES Songs(id) ON DELETE CASCADE,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (guild_id, song_id, tag)
+    );
+    ",
+    // 4: short unique names for songs, scoped by guild like the songs themselves
+    "\
+    CREATE TABLE SongAliases (
+        guild_id INTEGER NOT NULL,
+        alias TEXT NOT NULL,
+        song_id INTEGER NOT NULL REFERENCES Songs(id) ON DELETE CASCADE,
+        PRIMARY KEY (guild_id, alias)
+    );
+    ",
+    // 5: full text index over titles, aliases and tags, kept up to date by triggers.
+    // The trigram tokenizer allows to find words by parts of them, which makes typos tolerable.
+    "\
+    CREATE VIRTUAL TABLE SongSearch USING fts5(title, aliases, tags, tokenize = 'trigram');
+
+    INSERT INTO SongSearch (rowid, title, aliases, tags)
+    SELECT
+        s.id,
+        s.video_title,
+        (SELECT group_concat(alias, ' ') FROM SongAliases WHERE song_id = s.id),
+        (SELECT group_concat(tag, ' ') FROM SongTags WHERE song_id = s.id)
+    FROM Songs s;
+
+    CREATE TRIGGER SongSearchInsert AFTER INSERT ON Songs BEGIN
+        INSERT INTO SongSearch (rowid, title) VALUES (NEW.id, NEW.video_title);
+    END;
+
+    CREATE TRIGGER SongSearchUpdate AFTER UPDATE OF video_title ON Songs BEGIN
+        UPDATE SongSearch SET title = NEW.video_title WHERE rowid = NEW.id;
+    END;
+
+    CREATE TRIGGER SongSearchDelete AFTER DELETE ON Songs BEGIN
+        DELETE FROM SongSearch WHERE rowid = OLD.id;
+    END;
+
+    CREATE TRIGGER SongSearchAliasInsert AFTER INSERT ON SongAliases BEGIN
+        UPDATE SongSearch SET aliases = (SELECT group_concat(alias, ' ') FROM SongAliases WHERE song_id = NEW.song_id) WHERE rowid = NEW.song_id;
+    END;
+
+    CREATE TRIGGER SongSearchAliasDelete AFTER DELETE ON SongAliases BEGIN
+        UPDATE SongSearch SET aliases = (SELECT group_concat(alias, ' ') FROM SongAliases WHERE song_id = OLD.song_id) WHERE rowid = OLD.song_id;
+    END;
+
+    CREATE TRIGGER SongSearchTagInsert AFTER INSERT ON SongTags BEGIN
+        UPDATE SongSearch SET tags = (SELECT group_concat(tag, ' ') FROM SongTags WHERE song_id = NEW.song_id) WHERE rowid = NEW.song_id;
+    END;
+
+    CREATE TRIGGER SongSearchTagDelete AFTER DELETE ON SongTags BEGIN
+        UPDATE SongSearch SET tags = (SELECT group_concat(tag, ' ') FROM SongTags WHERE song_id = OLD.song_id) WHERE rowid = OLD.song_id;
+    END;
+    ",
+    // 6: named playlists of registered songs
+    "\
+    CREATE TABLE Playlists (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        guild_id INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        UNIQUE (guild_id, name)
+    );
+
+    CREATE TABLE PlaylistSongs (
+        playlist_id INTEGER NOT NULL REFERENCES Playlists(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        song_id INTEGER NOT NULL REFERENCES Songs(id) ON DELETE CASCADE
+    );
+
+    CREATE INDEX PlaylistSongsByPlaylist ON PlaylistSongs (playlist_id, position);
+    ",
+    // 7: scenes, which are saved queues that can be restored with one command together with the volume
+    // and loop mode. Tracks remember their registered song, to restore its volume offset, loudness and download.
+    "\
+    CREATE TABLE Scenes (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        guild_id INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        volume INTEGER,
+        loop_mode TEXT,
+        UNIQUE (guild_id, name)
+    );
+
+    CREATE TABLE SceneTracks (
+        scene_id INTEGER NOT NULL REFERENCES Scenes(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        url TEXT NOT NULL,
+        title TEXT NOT NULL,
+        song_id INTEGER
+    );
+    ",
+    // 8: settings which apply to everything played in a guild
+    "\
+    CREATE TABLE GuildSettings (
+        guild_id INTEGER PRIMARY KEY,
+        fade_seconds REAL NOT NULL DEFAULT 0
+    );
+    ",
+    // 9: short sound effects which are played on top of the music, from a URL or a local file
+    "\
+    CREATE TABLE SoundEffects (
+        guild_id INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        source TEXT NOT NULL,
+        PRIMARY KEY (guild_id, name)
+    );
+    ",
+    // 10: songs can be local files instead of YouTube videos. Rebuilding the table drops its
+    // search triggers, so they are created again.
+    "\
+    CREATE TABLE SongsWithFiles (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        guild_id INTEGER NOT NULL,
+        video_id TEXT,
+        file_path TEXT,
+        video_title TEXT,
+        UNIQUE (guild_id, video_id),
+        UNIQUE (guild_id, file_path),
+        CHECK (video_id IS NOT NULL OR file_path IS NOT NULL)
+    );
+    INSERT INTO SongsWithFiles (id, guild_id, video_id, video_title) SELECT id, guild_id, video_id, video_title FROM Songs;
+    DROP TABLE Songs;
+    ALTER TABLE SongsWithFiles RENAME TO Songs;
+
+    CREATE TRIGGER SongSearchInsert AFTER INSERT ON Songs BEGIN
+        INSERT INTO SongSearch (rowid, title) VALUES (NEW.id, NEW.video_title);
+    END;
+
+    CREATE TRIGGER SongSearchUpdate AFTER UPDATE OF video_title ON Songs BEGIN
+        UPDATE SongSearch SET title = NEW.video_title WHERE rowid = NEW.id;
+    END;
+
+    CREATE TRIGGER SongSearchDelete AFTER DELETE ON Songs BEGIN
+        DELETE FROM SongSearch WHERE rowid = OLD.id;
+    END;
+
+    ALTER TABLE SceneTracks RENAME COLUMN url TO location;
+    ",
+    // 11: downloaded copies of YouTube songs and when songs were played last, to evict old downloads first
+    "\
+    ALTER TABLE Songs ADD COLUMN cache_path TEXT;
+    ALTER TABLE Songs ADD COLUMN cache_checksum TEXT;
+    ALTER TABLE Songs ADD COLUMN cache_size INTEGER;
+    ALTER TABLE Songs ADD COLUMN last_used INTEGER;
+    ",
+    // 12: whether the current track or the whole queue repeats
+    "\
+    ALTER TABLE GuildSettings ADD COLUMN loop_mode TEXT NOT NULL DEFAULT 'off';
+    ",
+    // 13: volume of everything played in a guild and how much louder or quieter single songs are played
+    // there. Like tags, the offsets of global songs are set by each guild on its own.
+    "\
+    ALTER TABLE GuildSettings ADD COLUMN volume INTEGER NOT NULL DEFAULT 100;
+
+    CREATE TABLE SongVolumes (
+        guild_id INTEGER NOT NULL,
+        song_id INTEGER NOT NULL REFERENCES Songs(id) ON DELETE CASCADE,
+        volume_offset INTEGER NOT NULL,
+        PRIMARY KEY (guild_id, song_id)
+    );
+    ",
+    // 14: gain in dB which brings a song to the same loudness as all others, unknown until it was analyzed
+    "\
+    ALTER TABLE Songs ADD COLUMN normalization_gain REAL;
+    ",
+];
+
+/// Columns of a song in the order [`Database::map_song`] reads them, selected from [`SONG_TABLES`]
+const SONG_COLUMNS: &str = "s.id, s.video_id, s.file_path, s.video_title, s.cache_path, s.cache_checksum, s.cache_size, COALESCE(v.volume_offset, 0), s.normalization_gain";
+
+/// Songs joined with their volume offsets in the guild which is bound to the first parameter of the query
+const SONG_TABLES: &str = "Songs s LEFT JOIN SongVolumes v ON v.song_id = s.id AND v.guild_id = ?1";
+
+/// Amount of songs the full text search hands to the fuzzy ranking
+const SEARCH_CANDIDATES: usize = 50;
+
+/// Guild id of the library which is shared between all guilds the bot is part of.
+pub const GLOBAL_LIBRARY: u64 = 0;
+
+/// A song registered in the library of a guild or in the global library.
+/// It is either a YouTube video or a local file.
+#[derive(Clone, Debug)]
+pub struct Song {
+    pub id: i64,
+    pub video_id: Option<String>,
+    pub file_path: Option<String>,
+    pub title: String,
+    /// a downloaded copy of a YouTube song, which is preferred over streaming
+    pub cache: Option<CachedFile>,
+    /// in percent, how much louder or quieter the song is played than the volume of the guild
+    pub volume_offset: i32,
+    /// in dB, how much the song is raised or lowered to be as loud as all other songs
+    pub normalization_gain: Option<f64>,
+}
+
+/// A song downloaded to disk
+#[derive(Clone, Debug)]
+pub struct CachedFile {
+    pub path: String,
+    /// SHA-1 of the file, to detect if it was altered or damaged
+    pub checksum: String,
+    pub size: u64,
+}
+
+impl Song {
+    /// The URL of the video or the path of the file, which is used to play the song
+    pub fn location(&self) -> String {
+        match (&self.video_id, &self.file_path) {
+            (Some(video_id), _) => format!("https://www.youtube.com/watch?v={video_id}"),
+            (None, Some(file_path)) => file_path.clone(),
+            (None, None) => unreachable!("the database ensures every song has a video or a file")
+        }
+    }
+}
+
+/// Settings which apply to everything played in a guild
+#[derive(Clone, Copy, Debug)]
+pub struct GuildSettings {
+    /// how long tracks fade in and out, zero if they should not fade
+    pub fade: Duration,
+    pub loop_mode: LoopMode,
+    /// in percent, 100 plays tracks unchanged
+    pub volume: u32,
+}
+
+impl Default for GuildSettings {
+    fn default() -> Self {
+        GuildSettings {
+            fade: Duration::ZERO,
+            loop_mode: LoopMode::Off,
+            volume: 100,
+        }
+    }
+}
+
+/// What is repeated once a track ends
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LoopMode {
+    #[default]
+    Off,
+    /// the current track starts over
+    Track,
+    /// finished tracks are added to the end of the queue again
+    Queue,
+}
+
+impl LoopMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LoopMode::Off => "off",
+            LoopMode::Track => "track",
+            LoopMode::Queue => "queue",
+        }
+    }
+}
+
+impl FromStr for LoopMode {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.to_lowercase().as_str() {
+            "off" => Ok(LoopMode::Off),
+            "track" => Ok(LoopMode::Track),
+            "queue" => Ok(LoopMode::Queue),
+            _ => Err(format!("Unknown loop mode '{input}', must be track, queue or off"))
+        }
+    }
+}
+
+/// A saved queue which can be restored with one command
+#[derive(Clone, Debug)]
+pub struct Scene {
+    pub tracks: Vec<SceneTrack>,
+    /// volume of the guild when the scene was saved, unknown for older scenes
+    pub volume: Option<u32>,
+    pub loop_mode: Option<LoopMode>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SceneTrack {
+    /// URL of a video or path of a local file
+    pub location: String,
+    pub title: String,
+    /// the registered song which was queued, if it was not played by URL
+    pub song_id: Option<i64>,
+}
 
 pub struct Database(Connection);
 
 impl Database {
     pub fn open() -> Self {
-        let connection = Connection::open(Self::get_path()).expect("Failed to open db");
-
-        connection.execute("\
-        CREATE TABLE IF NOT EXISTS Songs (
-            video_id TEXT PRIMARY KEY,
-            video_title TEXT
-        )
-        ", []).expect("Failed to create songs table");
+        let mut connection = Connection::open(Self::get_path()).expect("Failed to open db");
+        Self::migrate(&mut connection).expect("Failed to migrate database");
 
         Database(connection)
     }
@@ -24,41 +341,697 @@ impl Database {
         path
     }
 
-    /// Add the given video id and title to the database
+    /// Bring the schema of the given connection to the latest version by applying all
+    /// migrations which were not applied yet. Every migration runs in its own transaction
+    /// together with the update of the stored schema version.
+    fn migrate(connection: &mut Connection) -> Result<(), String> {
+        connection.execute("\
+        CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER NOT NULL
+        )
+        ", []).map_err(Self::map_db_error)?;
+
+        let current_version: usize = connection
+            .query_row("SELECT COALESCE(MAX(version), 0) FROM schema_version", [], |row| row.get(0))
+            .map_err(Self::map_db_error)?;
+
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(current_version) {
+            let version = index + 1;
+            let transaction = connection.transaction().map_err(Self::map_db_error)?;
+
+            transaction.execute_batch(migration).map_err(|e| format!("Migration {version} failed: {e:?}"))?;
+            transaction.execute("INSERT INTO schema_version (version) VALUES (?1)", [version]).map_err(Self::map_db_error)?;
+            transaction.commit().map_err(Self::map_db_error)?;
+
+            println!("Applied database migration {version}");
+        }
+
+        Ok(())
+    }
+
+    /// Add the given video id and title to the library of the given guild and return the id of the new song
     pub fn add_song(
         &self,
+        guild_id: u64,
         video_id: String,
         video_title: String
-    ) -> Result<(), String> {
+    ) -> Result<i64, String> {
          self.0.execute("\
-            INSERT INTO Songs (video_id, video_title) VALUES (?1, ?2);
-        ", &[&video_id, &video_title]).map_err(Self::map_db_error)?;
+            INSERT INTO Songs (guild_id, video_id, video_title) VALUES (?1, ?2, ?3);
+        ", (guild_id, &video_id, &video_title)).map_err(Self::map_db_error)?;
+
+        Ok(self.0.last_insert_rowid())
+    }
+
+    /// Add a local audio file to the library of the given guild and return the id of the new song
+    pub fn add_local_song(&self, guild_id: u64, file_path: &str, title: &str) -> Result<i64, String> {
+        self.0.execute("\
+            INSERT INTO Songs (guild_id, file_path, video_title) VALUES (?1, ?2, ?3);
+        ", (guild_id, file_path, title)).map_err(Self::map_db_error)?;
+
+        Ok(self.0.last_insert_rowid())
+    }
+
+    /// Bring the local files in the global library up to date with the files scanned in the given directory.
+    /// New files are added, titles are updated, new genre tags are attached and songs of files in the directory
+    /// which no longer exist are removed. Tags are never removed from existing songs, as they could have been
+    /// added by hand. Files outside of it, like uploaded songs, are kept. Returns the amount of
+    /// indexed and removed files.
+    pub fn index_local_files(&self, directory: &Path, files: &[LocalFile]) -> Result<(usize, usize), String> {
+        let transaction = self.0.unchecked_transaction().map_err(Self::map_db_error)?;
+
+        for file in files {
+            let song_id: i64 = transaction.query_row("\
+                INSERT INTO Songs (guild_id, file_path, video_title) VALUES (?1, ?2, ?3)
+                ON CONFLICT (guild_id, file_path) DO UPDATE SET video_title = excluded.video_title
+                RETURNING id
+            ", (GLOBAL_LIBRARY, file.path.to_string_lossy(), &file.title), |row| row.get(0)).map_err(Self::map_db_error)?;
+
+            for tag in &file.tags {
+                transaction.execute("\
+                    INSERT OR IGNORE INTO SongTags (guild_id, song_id, tag) VALUES (?1, ?2, ?3);
+                ", (GLOBAL_LIBRARY, song_id, tag)).map_err(Self::map_db_error)?;
+            }
+        }
+
+        let indexed_paths = files.iter().map(|file| file.path.to_string_lossy().to_string()).collect::<HashSet<_>>();
+        let stored_files = {
+            let mut statement = transaction.prepare("\
+                SELECT id, file_path FROM Songs WHERE guild_id = ?1 AND file_path IS NOT NULL
+            ").map_err(Self::map_db_error)?;
+
+            let result = statement.query_map([GLOBAL_LIBRARY], |row| Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?
+            ))).map_err(Self::map_db_error)?;
+
+            result.map(|r| r.unwrap()).collect::<Vec<_>>()
+        };
+
+        let removed_ids = stored_files
+            .into_iter()
+            .filter(|(_, path)| Path::new(path).starts_with(directory) && !indexed_paths.contains(path))
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+
+        for song_id in &removed_ids {
+            Self::delete_song(&transaction, *song_id).map_err(Self::map_db_error)?;
+        }
+
+        transaction.commit().map_err(Self::map_db_error)?;
+
+        Ok((files.len(), removed_ids.len()))
+    }
+
+    /// Delete a song together with everything referencing it
+    fn delete_song(connection: &Connection, song_id: i64) -> Result<(), Error> {
+        connection.execute("DELETE FROM SongTags WHERE song_id = ?1", [song_id])?;
+        connection.execute("DELETE FROM SongVolumes WHERE song_id = ?1", [song_id])?;
+        connection.execute("DELETE FROM SongAliases WHERE song_id = ?1", [song_id])?;
+        // remove the song from the back of each playlist first, so the positions of later entries stay valid
+        let entries = {
+            let mut statement = connection.prepare("\
+                SELECT playlist_id, position FROM PlaylistSongs WHERE song_id = ?1 ORDER BY position DESC
+            ")?;
+            let result = statement.query_map([song_id], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))?;
+            result.collect::<Result<Vec<_>, _>>()?
+        };
+
+        for (playlist_id, position) in entries {
+            connection.execute("DELETE FROM PlaylistSongs WHERE playlist_id = ?1 AND position = ?2", (playlist_id, position))?;
+            connection.execute("\
+                UPDATE PlaylistSongs SET position = position - 1 WHERE playlist_id = ?1 AND position > ?2
+            ", (playlist_id, position))?;
+        }
+
+        connection.execute("DELETE FROM Songs WHERE id = ?1", [song_id])?;
 
         Ok(())
     }
 
-    pub fn find_videos_like(&self, input: String) -> Vec<(String, String)> {
-        match self.get_all_videos_and_titles() {
-            Ok(iter) => iter
+    /// Give a song an alias in the library of the given guild. Fails if the alias is already taken.
+    pub fn add_alias(&self, guild_id: u64, alias: &str, song_id: i64) -> Result<(), String> {
+        self.0.execute("\
+            INSERT INTO SongAliases (guild_id, alias, song_id) VALUES (?1, ?2, ?3);
+        ", (guild_id, alias, song_id)).map_err(|e| match e {
+            Error::SqliteFailure(failure, _) if failure.code == rusqlite::ErrorCode::ConstraintViolation => format!("The alias '{alias}' is already taken"),
+            e => Self::map_db_error(e)
+        })?;
+
+        Ok(())
+    }
+
+    /// Find the song with the given alias. Aliases of the guild take precedence over global ones.
+    pub fn find_song_by_alias(&self, guild_id: u64, alias: &str) -> Result<Option<Song>, String> {
+        self.0.query_row(&format!("\
+            SELECT {SONG_COLUMNS} FROM {SONG_TABLES}
+            JOIN SongAliases a ON a.song_id = s.id
+            WHERE a.guild_id IN (?1, ?2) AND a.alias = ?3
+            ORDER BY a.guild_id = ?2
+        "), (guild_id, GLOBAL_LIBRARY, alias), Self::map_song).optional().map_err(Self::map_db_error)
+    }
+
+    /// Load the song with the given id, with the volume offset of the given guild
+    pub fn get_song(&self, guild_id: u64, song_id: i64) -> Result<Option<Song>, String> {
+        self.0.query_row(&format!("\
+            SELECT {SONG_COLUMNS} FROM {SONG_TABLES}
+            WHERE s.id = ?2
+        "), (guild_id, song_id), Self::map_song).optional().map_err(Self::map_db_error)
+    }
+
+    /// Find all songs whose title matches the given input, ranked by their score with the best
+    /// match first. If the input is an alias, only the song with this alias is returned. The library
+    /// of the given guild is searched first, the global library is only used if the guild has no
+    /// matching song.
+    pub fn find_videos_like(&self, guild_id: u64, input: String) -> Vec<(Song, f64)> {
+        if let Ok(Some(song)) = self.find_song_by_alias(guild_id, &input.to_lowercase()) {
+            return vec![(song, 1.0)]
+        }
+
+        let guild_songs = self.find_videos_like_in_library(guild_id, guild_id, &input);
+
+        match guild_songs.is_empty() && guild_id != GLOBAL_LIBRARY {
+            true => self.find_videos_like_in_library(guild_id, GLOBAL_LIBRARY, &input),
+            false => guild_songs
+        }
+    }
+
+    fn find_videos_like_in_library(&self, guild_id: u64, library: u64, input: &str) -> Vec<(Song, f64)> {
+        match self.get_search_candidates(guild_id, library, input) {
+            Ok(candidates) => candidates
                 .into_iter()
-                .filter(|(_, title)| title.to_lowercase().contains(&input.to_lowercase()))
+                .map(|(song, aliases, tags)| {
+                    let score = search::score(input, &song.title)
+                        .max(search::score(input, &aliases))
+                        .max(search::score(input, &tags) * search::TAG_WEIGHT);
+                    (song, score)
+                })
+                .filter(|(_, score)| *score >= search::MIN_SCORE)
+                .sorted_by(|(_, a), (_, b)| b.total_cmp(a))
                 .collect(),
             Err(_) => vec![]
         }
     }
 
-    fn get_all_videos_and_titles(&self) -> Result<Vec<(String, String)>, String> {
-        let mut statement = self.0.prepare("SELECT * FROM Songs").map_err(Self::map_db_error)?;
+    /// Use the full text index to find the songs of a library which share at least some parts of
+    /// words with the input, together with their aliases and tags visible to the given guild.
+    /// Returns the best candidates only, or every song of the library if the input is too short to search the index.
+    fn get_search_candidates(&self, guild_id: u64, library: u64, input: &str) -> Result<Vec<(Song, String, String)>, String> {
+        let fts_query = search::trigram_query(input);
+
+        // words shorter than three characters have no trigrams, so they can only be found by ranking all songs
+        let candidates = match fts_query.is_empty() {
+            true => {
+                let mut statement = self.0.prepare(&format!("\
+                    SELECT {SONG_COLUMNS},
+                        (SELECT group_concat(alias, ' ') FROM SongAliases a WHERE a.song_id = s.id AND a.guild_id IN (?1, ?2)),
+                        (SELECT group_concat(tag, ' ') FROM SongTags t WHERE t.song_id = s.id AND t.guild_id IN (?1, ?2))
+                    FROM {SONG_TABLES}
+                    WHERE s.guild_id = ?3
+                ")).map_err(Self::map_db_error)?;
+
+                let result = statement.query_map((guild_id, GLOBAL_LIBRARY, library), Self::map_candidate).map_err(Self::map_db_error)?;
+                result.map(|r| r.unwrap()).collect()
+            },
+            false => {
+                let mut statement = self.0.prepare(&format!("\
+                    SELECT {SONG_COLUMNS},
+                        (SELECT group_concat(alias, ' ') FROM SongAliases a WHERE a.song_id = s.id AND a.guild_id IN (?1, ?2)),
+                        (SELECT group_concat(tag, ' ') FROM SongTags t WHERE t.song_id = s.id AND t.guild_id IN (?1, ?2))
+                    FROM {SONG_TABLES}
+                    JOIN SongSearch f ON f.rowid = s.id
+                    WHERE SongSearch MATCH ?4 AND s.guild_id = ?3
+                    ORDER BY f.rank
+                    LIMIT ?5
+                ")).map_err(Self::map_db_error)?;
+
+                let result = statement.query_map((guild_id, GLOBAL_LIBRARY, library, fts_query, SEARCH_CANDIDATES), Self::map_candidate).map_err(Self::map_db_error)?;
+                result.map(|r| r.unwrap()).collect()
+            }
+        };
+
+        Ok(candidates)
+    }
+
+    /// Attach the given tag to a song for the given guild. Adding a tag twice has no effect.
+    pub fn add_tag(&self, guild_id: u64, song_id: i64, tag: &str) -> Result<(), String> {
+        self.0.execute("\
+            INSERT OR IGNORE INTO SongTags (guild_id, song_id, tag) VALUES (?1, ?2, ?3);
+        ", (guild_id, song_id, tag)).map_err(Self::map_db_error)?;
+
+        Ok(())
+    }
+
+    /// Remove a tag the given guild attached to a song. Returns false if the guild did not tag the song like this.
+    pub fn remove_tag(&self, guild_id: u64, song_id: i64, tag: &str) -> Result<bool, String> {
+        let removed = self.0.execute("\
+            DELETE FROM SongTags WHERE guild_id = ?1 AND song_id = ?2 AND tag = ?3;
+        ", (guild_id, song_id, tag)).map_err(Self::map_db_error)?;
+
+        Ok(removed > 0)
+    }
+
+    /// Get all tags of the given song visible to the guild, sorted by name
+    pub fn get_tags_of_song(&self, guild_id: u64, song_id: i64) -> Result<Vec<String>, String> {
+        let mut statement = self.0.prepare("\
+            SELECT DISTINCT tag FROM SongTags WHERE guild_id IN (?1, ?2) AND song_id = ?3 ORDER BY tag
+        ").map_err(Self::map_db_error)?;
+
+        let result = statement.query_map((guild_id, GLOBAL_LIBRARY, song_id), |row| row.get(0)).map_err(Self::map_db_error)?;
+
+        Ok(result.map(|r| r.unwrap()).collect())
+    }
+
+    /// Get all tags used in the library of the given guild and the global library,
+    /// together with the amount of songs having the tag
+    pub fn get_tags_of_library(&self, guild_id: u64) -> Result<Vec<(String, usize)>, String> {
+        let mut statement = self.0.prepare("\
+            SELECT t.tag, COUNT(DISTINCT t.song_id) FROM SongTags t
+            JOIN Songs s ON s.id = t.song_id
+            WHERE s.guild_id IN (?1, ?2) AND t.guild_id IN (?1, ?2)
+            GROUP BY t.tag ORDER BY t.tag
+        ").map_err(Self::map_db_error)?;
+
+        let result = statement.query_map([guild_id, GLOBAL_LIBRARY], |row| Ok((
+            row.get(0)?,
+            row.get(1)?
+        ))).map_err(Self::map_db_error)?;
+
+        Ok(result.map(|r| r.unwrap()).collect())
+    }
+
+    /// Find all songs of the guild library and the global library with the given tag, in random order
+    pub fn find_songs_with_tag(&self, guild_id: u64, tag: &str) -> Result<Vec<Song>, String> {
+        let mut statement = self.0.prepare(&format!("\
+            SELECT {SONG_COLUMNS} FROM {SONG_TABLES}
+            WHERE s.guild_id IN (?1, ?2) AND EXISTS (
+                SELECT 1 FROM SongTags t WHERE t.song_id = s.id AND t.guild_id IN (?1, ?2) AND t.tag = ?3
+            )
+            ORDER BY RANDOM()
+        ")).map_err(Self::map_db_error)?;
+
+        let result = statement.query_map((guild_id, GLOBAL_LIBRARY, tag), Self::map_song).map_err(Self::map_db_error)?;
+
+        Ok(result.map(|r| r.unwrap()).collect())
+    }
+
+    /// Create a new empty playlist in the given guild. Fails if a playlist with this name already exists.
+    pub fn create_playlist(&self, guild_id: u64, name: &str) -> Result<(), String> {
+        self.0.execute("\
+            INSERT INTO Playlists (guild_id, name) VALUES (?1, ?2);
+        ", (guild_id, name)).map_err(|e| match e {
+            Error::SqliteFailure(failure, _) if failure.code == rusqlite::ErrorCode::ConstraintViolation => format!("A playlist named '{name}' already exists"),
+            e => Self::map_db_error(e)
+        })?;
+
+        Ok(())
+    }
+
+    /// Delete the playlist with the given name and all its entries. Returns false if no such playlist exists.
+    pub fn delete_playlist(&self, guild_id: u64, name: &str) -> Result<bool, String> {
+        let playlist_id = match self.find_playlist(guild_id, name)? {
+            Some(playlist_id) => playlist_id,
+            None => return Ok(false)
+        };
+
+        self.0.execute("DELETE FROM PlaylistSongs WHERE playlist_id = ?1", [playlist_id]).map_err(Self::map_db_error)?;
+        self.0.execute("DELETE FROM Playlists WHERE id = ?1", [playlist_id]).map_err(Self::map_db_error)?;
+
+        Ok(true)
+    }
+
+    /// Get the id of the playlist with the given name in the given guild
+    pub fn find_playlist(&self, guild_id: u64, name: &str) -> Result<Option<i64>, String> {
+        self.0.query_row("\
+            SELECT id FROM Playlists WHERE guild_id = ?1 AND name = ?2
+        ", (guild_id, name), |row| row.get(0)).optional().map_err(Self::map_db_error)
+    }
+
+    /// Get the names of all playlists of the given guild together with their amount of songs
+    pub fn get_playlists(&self, guild_id: u64) -> Result<Vec<(String, usize)>, String> {
+        let mut statement = self.0.prepare("\
+            SELECT p.name, COUNT(ps.song_id) FROM Playlists p
+            LEFT JOIN PlaylistSongs ps ON ps.playlist_id = p.id
+            WHERE p.guild_id = ?1
+            GROUP BY p.id ORDER BY p.name
+        ").map_err(Self::map_db_error)?;
+
+        let result = statement.query_map([guild_id], |row| Ok((
+            row.get(0)?,
+            row.get(1)?
+        ))).map_err(Self::map_db_error)?;
+
+        Ok(result.map(|r| r.unwrap()).collect())
+    }
+
+    /// Append a song to the end of a playlist and return its position, starting at 1
+    pub fn add_song_to_playlist(&self, playlist_id: i64, song_id: i64) -> Result<usize, String> {
+        self.0.query_row("\
+            INSERT INTO PlaylistSongs (playlist_id, position, song_id)
+            SELECT ?1, COALESCE(MAX(position), 0) + 1, ?2 FROM PlaylistSongs WHERE playlist_id = ?1
+            RETURNING position
+        ", (playlist_id, song_id), |row| row.get(0)).map_err(Self::map_db_error)
+    }
+
+    /// Remove the song at the given position from a playlist and move the following songs up.
+    /// Returns false if the playlist has no song at this position.
+    pub fn remove_song_from_playlist(&self, playlist_id: i64, position: usize) -> Result<bool, String> {
+        let removed = self.0.execute("\
+            DELETE FROM PlaylistSongs WHERE playlist_id = ?1 AND position = ?2
+        ", (playlist_id, position)).map_err(Self::map_db_error)?;
+
+        if removed == 0 {
+            return Ok(false)
+        }
+
+        self.0.execute("\
+            UPDATE PlaylistSongs SET position = position - 1 WHERE playlist_id = ?1 AND position > ?2
+        ", (playlist_id, position)).map_err(Self::map_db_error)?;
+
+        Ok(true)
+    }
+
+    /// Get the songs of a playlist of the given guild in their order
+    pub fn get_playlist_songs(&self, guild_id: u64, playlist_id: i64) -> Result<Vec<Song>, String> {
+        let mut statement = self.0.prepare(&format!("\
+            SELECT {SONG_COLUMNS} FROM {SONG_TABLES}
+            JOIN PlaylistSongs ps ON ps.song_id = s.id
+            WHERE ps.playlist_id = ?2
+            ORDER BY ps.position
+        ")).map_err(Self::map_db_error)?;
+
+        let result = statement.query_map((guild_id, playlist_id), Self::map_song).map_err(Self::map_db_error)?;
+
+        Ok(result.map(|r| r.unwrap()).collect())
+    }
+
+    /// Store a scene under the given name. An existing scene with the same name is replaced.
+    pub fn save_scene(&self, guild_id: u64, name: &str, scene: &Scene) -> Result<(), String> {
+        let transaction = self.0.unchecked_transaction().map_err(Self::map_db_error)?;
+
+        transaction.execute("\
+            DELETE FROM SceneTracks WHERE scene_id IN (SELECT id FROM Scenes WHERE guild_id = ?1 AND name = ?2)
+        ", (guild_id, name)).map_err(Self::map_db_error)?;
+        transaction.execute("\
+            DELETE FROM Scenes WHERE guild_id = ?1 AND name = ?2
+        ", (guild_id, name)).map_err(Self::map_db_error)?;
+        transaction.execute("\
+            INSERT INTO Scenes (guild_id, name, volume, loop_mode) VALUES (?1, ?2, ?3, ?4)
+        ", (guild_id, name, scene.volume, scene.loop_mode.map(|loop_mode| loop_mode.name()))).map_err(Self::map_db_error)?;
+
+        let scene_id = transaction.last_insert_rowid();
+
+        for (position, track) in scene.tracks.iter().enumerate() {
+            transaction.execute("\
+                INSERT INTO SceneTracks (scene_id, position, location, title, song_id) VALUES (?1, ?2, ?3, ?4, ?5)
+            ", (scene_id, position, &track.location, &track.title, track.song_id)).map_err(Self::map_db_error)?;
+        }
+
+        transaction.commit().map_err(Self::map_db_error)
+    }
+
+    /// Load the scene with the given name
+    pub fn get_scene(&self, guild_id: u64, name: &str) -> Result<Option<Scene>, String> {
+        let scene: Option<(i64, Option<u32>, Option<String>)> = self.0.query_row("\
+            SELECT id, volume, loop_mode FROM Scenes WHERE guild_id = ?1 AND name = ?2
+        ", (guild_id, name), |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).optional().map_err(Self::map_db_error)?;
+
+        let (scene_id, volume, loop_mode) = match scene {
+            Some(scene) => scene,
+            None => return Ok(None)
+        };
+
+        let mut statement = self.0.prepare("\
+            SELECT location, title, song_id FROM SceneTracks WHERE scene_id = ?1 ORDER BY position
+        ").map_err(Self::map_db_error)?;
+
+        let result = statement.query_map([scene_id], |row| Ok(SceneTrack {
+            location: row.get(0)?,
+            title: row.get(1)?,
+            song_id: row.get(2)?,
+        })).map_err(Self::map_db_error)?;
+
+        Ok(Some(Scene {
+            tracks: result.map(|r| r.unwrap()).collect(),
+            volume,
+            loop_mode: loop_mode.and_then(|loop_mode| loop_mode.parse().ok()),
+        }))
+    }
+
+    /// Get the names of all scenes of the given guild
+    pub fn get_scenes(&self, guild_id: u64) -> Result<Vec<String>, String> {
+        let mut statement = self.0.prepare("\
+            SELECT name FROM Scenes WHERE guild_id = ?1 ORDER BY name
+        ").map_err(Self::map_db_error)?;
+
+        let result = statement.query_map([guild_id], |row| row.get(0)).map_err(Self::map_db_error)?;
+
+        Ok(result.map(|r| r.unwrap()).collect())
+    }
+
+    /// Delete the scene with the given name. Returns false if no such scene exists.
+    pub fn delete_scene(&self, guild_id: u64, name: &str) -> Result<bool, String> {
+        self.0.execute("\
+            DELETE FROM SceneTracks WHERE scene_id IN (SELECT id FROM Scenes WHERE guild_id = ?1 AND name = ?2)
+        ", (guild_id, name)).map_err(Self::map_db_error)?;
+
+        let deleted = self.0.execute("\
+            DELETE FROM Scenes WHERE guild_id = ?1 AND name = ?2
+        ", (guild_id, name)).map_err(Self::map_db_error)?;
+
+        Ok(deleted > 0)
+    }
+
+    /// Register a sound effect in the given guild. Fails if the name is already taken.
+    pub fn add_sound_effect(&self, guild_id: u64, name: &str, source: &str) -> Result<(), String> {
+        self.0.execute("\
+            INSERT INTO SoundEffects (guild_id, name, source) VALUES (?1, ?2, ?3);
+        ", (guild_id, name, source)).map_err(|e| match e {
+            Error::SqliteFailure(failure, _) if failure.code == rusqlite::ErrorCode::ConstraintViolation => format!("A sound effect named '{name}' already exists"),
+            e => Self::map_db_error(e)
+        })?;
+
+        Ok(())
+    }
+
+    /// Remove a sound effect and return its source, or None if no such sound effect exists
+    pub fn remove_sound_effect(&self, guild_id: u64, name: &str) -> Result<Option<String>, String> {
+        self.0.query_row("\
+            DELETE FROM SoundEffects WHERE guild_id = ?1 AND name = ?2 RETURNING source
+        ", (guild_id, name), |row| row.get(0)).optional().map_err(Self::map_db_error)
+    }
+
+    /// Get the source of the sound effect with the given name
+    pub fn find_sound_effect(&self, guild_id: u64, name: &str) -> Result<Option<String>, String> {
+        self.0.query_row("\
+            SELECT source FROM SoundEffects WHERE guild_id = ?1 AND name = ?2
+        ", (guild_id, name), |row| row.get(0)).optional().map_err(Self::map_db_error)
+    }
+
+    /// Get the names of all sound effects of the given guild
+    pub fn get_sound_effects(&self, guild_id: u64) -> Result<Vec<String>, String> {
+        let mut statement = self.0.prepare("\
+            SELECT name FROM SoundEffects WHERE guild_id = ?1 ORDER BY name
+        ").map_err(Self::map_db_error)?;
+
+        let result = statement.query_map([guild_id], |row| row.get(0)).map_err(Self::map_db_error)?;
+
+        Ok(result.map(|r| r.unwrap()).collect())
+    }
+
+    /// Store the downloaded copy of a song, or forget it if None is given
+    pub fn set_cached_file(&self, song_id: i64, cached_file: Option<&CachedFile>) -> Result<(), String> {
+        self.0.execute("\
+            UPDATE Songs SET cache_path = ?2, cache_checksum = ?3, cache_size = ?4 WHERE id = ?1
+        ", (
+            song_id,
+            cached_file.map(|cached_file| &cached_file.path),
+            cached_file.map(|cached_file| &cached_file.checksum),
+            cached_file.map(|cached_file| cached_file.size)
+        )).map_err(Self::map_db_error)?;
+
+        Ok(())
+    }
+
+    /// Get all downloaded songs, the least recently used first
+    pub fn get_cached_songs(&self) -> Result<Vec<(i64, CachedFile)>, String> {
+        let mut statement = self.0.prepare("\
+            SELECT id, cache_path, cache_checksum, cache_size FROM Songs
+            WHERE cache_path IS NOT NULL
+            ORDER BY COALESCE(last_used, 0)
+        ").map_err(Self::map_db_error)?;
 
         let result = statement.query_map([], |row| Ok((
-            row.get(0).unwrap(),
-            row.get(1).unwrap()
+            row.get(0)?,
+            CachedFile {
+                path: row.get(1)?,
+                checksum: row.get(2)?,
+                size: row.get(3)?,
+            }
         ))).map_err(Self::map_db_error)?;
 
         Ok(result.map(|r| r.unwrap()).collect())
     }
 
+    /// Get all YouTube songs of the guild library and the global library which were not downloaded yet
+    pub fn get_uncached_songs(&self, guild_id: u64) -> Result<Vec<Song>, String> {
+        let mut statement = self.0.prepare(&format!("\
+            SELECT {SONG_COLUMNS} FROM {SONG_TABLES}
+            WHERE s.guild_id IN (?1, ?2) AND s.video_id IS NOT NULL AND s.cache_path IS NULL
+        ")).map_err(Self::map_db_error)?;
+
+        let result = statement.query_map([guild_id, GLOBAL_LIBRARY], Self::map_song).map_err(Self::map_db_error)?;
+
+        Ok(result.map(|r| r.unwrap()).collect())
+    }
+
+    /// Remember that the given songs were just played
+    pub fn mark_songs_used(&self, songs: &[Song]) -> Result<(), String> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or_default();
+
+        for song in songs {
+            self.0.execute("UPDATE Songs SET last_used = ?2 WHERE id = ?1", (song.id, now)).map_err(Self::map_db_error)?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the settings of the given guild, or the default settings if none were stored yet
+    pub fn get_guild_settings(&self, guild_id: u64) -> Result<GuildSettings, String> {
+        let settings = self.0.query_row("\
+            SELECT fade_seconds, loop_mode, volume FROM GuildSettings WHERE guild_id = ?1
+        ", [guild_id], |row| Ok(GuildSettings {
+            fade: Duration::from_secs_f64(row.get(0)?),
+            loop_mode: row.get::<_, String>(1)?.parse().unwrap_or_default(),
+            volume: row.get(2)?,
+        })).optional().map_err(Self::map_db_error)?;
+
+        Ok(settings.unwrap_or_default())
+    }
+
+    /// Set how long tracks fade in and out in the given guild
+    pub fn set_fade(&self, guild_id: u64, fade: Duration) -> Result<(), String> {
+        self.0.execute("\
+            INSERT INTO GuildSettings (guild_id, fade_seconds) VALUES (?1, ?2)
+            ON CONFLICT (guild_id) DO UPDATE SET fade_seconds = excluded.fade_seconds
+        ", (guild_id, fade.as_secs_f64())).map_err(Self::map_db_error)?;
+
+        Ok(())
+    }
+
+    /// Set what is repeated once a track ends in the given guild
+    pub fn set_loop_mode(&self, guild_id: u64, loop_mode: LoopMode) -> Result<(), String> {
+        self.0.execute("\
+            INSERT INTO GuildSettings (guild_id, loop_mode) VALUES (?1, ?2)
+            ON CONFLICT (guild_id) DO UPDATE SET loop_mode = excluded.loop_mode
+        ", (guild_id, loop_mode.name())).map_err(Self::map_db_error)?;
+
+        Ok(())
+    }
+
+    /// Set the volume of everything played in the given guild, in percent
+    pub fn set_volume(&self, guild_id: u64, volume: u32) -> Result<(), String> {
+        self.0.execute("\
+            INSERT INTO GuildSettings (guild_id, volume) VALUES (?1, ?2)
+            ON CONFLICT (guild_id) DO UPDATE SET volume = excluded.volume
+        ", (guild_id, volume)).map_err(Self::map_db_error)?;
+
+        Ok(())
+    }
+
+    /// Set how much louder or quieter a song is played than the volume of the given guild, in percent
+    pub fn set_song_volume_offset(&self, guild_id: u64, song_id: i64, volume_offset: i32) -> Result<(), String> {
+        self.0.execute("\
+            INSERT INTO SongVolumes (guild_id, song_id, volume_offset) VALUES (?1, ?2, ?3)
+            ON CONFLICT (guild_id, song_id) DO UPDATE SET volume_offset = excluded.volume_offset
+        ", (guild_id, song_id, volume_offset)).map_err(Self::map_db_error)?;
+
+        Ok(())
+    }
+
+    /// Store the gain in dB which normalizes the loudness of the song
+    pub fn set_normalization_gain(&self, song_id: i64, gain: f64) -> Result<(), String> {
+        self.0.execute("UPDATE Songs SET normalization_gain = ?2 WHERE id = ?1", (song_id, gain)).map_err(Self::map_db_error)?;
+
+        Ok(())
+    }
+
+    fn map_song(row: &Row) -> rusqlite::Result<Song> {
+        Ok(Song {
+            id: row.get(0)?,
+            video_id: row.get(1)?,
+            file_path: row.get(2)?,
+            title: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+            cache: match (row.get(4)?, row.get(5)?, row.get(6)?) {
+                (Some(path), Some(checksum), Some(size)) => Some(CachedFile { path, checksum, size }),
+                _ => None
+            },
+            volume_offset: row.get(7)?,
+            normalization_gain: row.get(8)?,
+        })
+    }
+
+    /// Map a song followed by its aliases and tags, each joined by spaces
+    fn map_candidate(row: &Row) -> rusqlite::Result<(Song, String, String)> {
+        Ok((
+            Self::map_song(row)?,
+            row.get::<_, Option<String>>(9)?.unwrap_or_default(),
+            row.get::<_, Option<String>>(10)?.unwrap_or_default()
+        ))
+    }
+
     fn map_db_error(error: Error) -> String {
         format!("Failed to get videos due to error: {:?}", error)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrate_keeps_songs_of_the_first_schema() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch("\
+            CREATE TABLE Songs (video_id TEXT PRIMARY KEY, video_title TEXT);
+            INSERT INTO Songs (video_id, video_title) VALUES ('abc', 'Dragon Battle'), ('def', 'Tavern Music');
+        ").unwrap();
+
+        // the second run has nothing left to do
+        Database::migrate(&mut connection).unwrap();
+        Database::migrate(&mut connection).unwrap();
+
+        let version: usize = connection.query_row("SELECT MAX(version) FROM schema_version", [], |row| row.get(0)).unwrap();
+        let applied: usize = connection.query_row("SELECT COUNT(*) FROM schema_version", [], |row| row.get(0)).unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+        assert_eq!(applied, MIGRATIONS.len());
+
+        let songs = connection
+            .prepare("SELECT guild_id, video_id, video_title FROM Songs ORDER BY video_id").unwrap()
+            .query_map([], |row| Ok((row.get::<_, u64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))).unwrap()
+            .map(|r| r.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(songs, vec![
+            (GLOBAL_LIBRARY, "abc".to_string(), "Dragon Battle".to_string()),
+            (GLOBAL_LIBRARY, "def".to_string(), "Tavern Music".to_string()),
+        ]);
+
+        let triggers = connection
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'trigger' AND name LIKE 'SongSearch%' ORDER BY name").unwrap()
+            .query_map([], |row| row.get::<_, String>(0)).unwrap()
+            .map(|r| r.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(triggers, vec![
+            "SongSearchAliasDelete", "SongSearchAliasInsert", "SongSearchDelete", "SongSearchInsert",
+            "SongSearchTagDelete", "SongSearchTagInsert", "SongSearchUpdate",
+        ]);
+
+        // the index still follows the tables
+        let database = Database(connection);
+        let song_id = database.add_song(1, "ghi".to_string(), "Forest Ambience".to_string()).unwrap();
+        database.add_tag(1, song_id, "nature").unwrap();
+        let tagged: usize = database.0.query_row("SELECT COUNT(*) FROM SongSearch WHERE SongSearch MATCH 'tags:nature'", [], |row| row.get(0)).unwrap();
+        assert_eq!(tagged, 1);
+        assert_eq!(database.find_videos_like(0, "dragon".to_string())[0].0.title, "Dragon Battle");
+    }
+}
@@ -0,0 +1,149 @@
+use serenity::async_trait;
+use serenity::prelude::TypeMapKey;
+use songbird::tracks::{TrackHandle, TrackQueue};
+use songbird::{Event, EventContext};
+use std::time::{Duration, Instant};
+use crate::queue;
+
+/// How often the volume of a track is adjusted while it fades
+const FADE_STEP: Duration = Duration::from_millis(100);
+
+/// Fade settings and volume of a track, stored in its type map
+#[derive(Clone, Copy, Debug)]
+struct Fade {
+    /// how long fading in or out takes, zero if the track should not fade at all
+    length: Duration,
+    /// when the track started to fade out because it was skipped or stopped
+    out_since: Option<Instant>,
+    /// volume of the guild, 1 plays the track unchanged
+    volume: f32,
+    /// factor of the song on top of the volume of the guild
+    gain: f32,
+}
+
+impl Fade {
+    /// The volume of the track at the given position
+    fn volume(&self, position: Duration, duration: Option<Duration>) -> f32 {
+        self.envelope(position, duration) * self.volume * self.gain
+    }
+
+    /// How far the track is faded in at the given position, between 0 and 1
+    fn envelope(&self, position: Duration, duration: Option<Duration>) -> f32 {
+        if self.length.is_zero() {
+            return 1.0
+        }
+
+        let length = self.length.as_secs_f32();
+        let fade_in = position.as_secs_f32() / length;
+        let fade_out = duration
+            .map(|duration| duration.saturating_sub(position).as_secs_f32() / length)
+            .unwrap_or(1.0);
+        let skip_out = self.out_since
+            .map(|since| 1.0 - since.elapsed().as_secs_f32() / length)
+            .unwrap_or(1.0);
+
+        fade_in.min(fade_out).min(skip_out).clamp(0.0, 1.0)
+    }
+}
+
+struct FadeKey;
+
+impl TypeMapKey for FadeKey {
+    type Value = Fade;
+}
+
+/// Let the given track fade in when it starts and fade out before it ends, at the given volume and gain
+pub async fn attach(handle: &TrackHandle, length: Duration, volume: f32, gain: f32) {
+    handle.typemap().write().await.insert::<FadeKey>(Fade { length, out_since: None, volume, gain });
+    let _ = handle.add_event(Event::Periodic(FADE_STEP, None), FadeEnvelope);
+}
+
+/// Change the fade length of all given tracks
+pub async fn set_length(handles: &[TrackHandle], length: Duration) {
+    for handle in handles {
+        if let Some(fade) = handle.typemap().write().await.get_mut::<FadeKey>() {
+            fade.length = length;
+        }
+    }
+}
+
+/// Change the volume of all given tracks, keeping the gain of their songs
+pub async fn set_volume(handles: &[TrackHandle], volume: f32) {
+    for handle in handles {
+        if let Some(fade) = handle.typemap().write().await.get_mut::<FadeKey>() {
+            fade.volume = volume;
+        }
+    }
+}
+
+/// Change the gain of the song played by the given track
+pub async fn set_gain(handle: &TrackHandle, gain: f32) {
+    if let Some(fade) = handle.typemap().write().await.get_mut::<FadeKey>() {
+        fade.gain = gain;
+    }
+}
+
+/// Fade out the current track of the queue and skip it once it is silent
+pub async fn skip(queue: &TrackQueue) {
+    let current = match queue.current() {
+        Some(current) => current,
+        None => return
+    };
+
+    let length = start_fade_out(&current).await;
+
+    if length.is_zero() {
+        let _ = queue.skip();
+        return
+    }
+
+    let queue = queue.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(length).await;
+
+        // the track might have ended on its own in the meantime
+        if queue.current().map(|handle| handle.uuid()) == Some(current.uuid()) {
+            let _ = queue.skip();
+        }
+    });
+}
+
+/// Fade out the current track of the queue and wait until it is silent
+pub async fn fade_out_current(queue: &TrackQueue) {
+    if let Some(current) = queue.current() {
+        tokio::time::sleep(start_fade_out(&current).await).await;
+    }
+}
+
+/// Start to fade out the given track and return how long this takes
+async fn start_fade_out(handle: &TrackHandle) -> Duration {
+    match handle.typemap().write().await.get_mut::<FadeKey>() {
+        Some(fade) => {
+            fade.out_since = Some(Instant::now());
+            fade.length
+        },
+        None => Duration::ZERO
+    }
+}
+
+/// Periodically sets the volume of a track according to its fade settings and position
+struct FadeEnvelope;
+
+#[async_trait]
+impl songbird::events::EventHandler for FadeEnvelope {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        if let EventContext::Track(track_list) = ctx {
+            for (state, handle) in *track_list {
+                let fade = match handle.typemap().read().await.get::<FadeKey>() {
+                    Some(fade) => *fade,
+                    None => continue
+                };
+
+                let duration = queue::get_queued_song(handle).await.and_then(|song| song.duration);
+                let _ = handle.set_volume(fade.volume(state.position, duration));
+            }
+        }
+
+        None
+    }
+}
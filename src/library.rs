@@ -0,0 +1,122 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey};
+use symphonia::core::probe::Hint;
+
+/// Environment variable which points to the directory of local music files
+pub const MUSIC_DIRECTORY_VARIABLE: &str = "DMBOT_MUSIC_DIR";
+
+/// File extensions of the audio formats symphonia is able to decode
+const AUDIO_EXTENSIONS: &[&str] = &["mp3", "m4a", "mp4", "aac", "alac", "flac", "wav", "ogg"];
+
+/// An audio file found in the local music directory
+#[derive(Clone, Debug)]
+pub struct LocalFile {
+    pub path: PathBuf,
+    pub title: String,
+    /// the genres of the file, used as tags of the song
+    pub tags: Vec<String>,
+    pub duration: Option<Duration>,
+}
+
+/// Get the configured local music directory, if any
+pub fn get_music_directory() -> Option<PathBuf> {
+    std::env::var(MUSIC_DIRECTORY_VARIABLE).ok().map(PathBuf::from)
+}
+
+/// Find all audio files in the given directory and its subdirectories and read their tags.
+/// Files which can not be read are skipped.
+pub fn scan(directory: &Path) -> Result<Vec<LocalFile>, String> {
+    let mut files = vec![];
+    let mut directories = vec![directory.to_path_buf()];
+
+    while let Some(directory) = directories.pop() {
+        let entries = std::fs::read_dir(&directory).map_err(|e| format!("Could not read {}: {e}", directory.display()))?;
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+
+            if path.is_dir() {
+                directories.push(path);
+            } else if is_audio_file(&path) {
+                match read_file(&path) {
+                    Ok(file) => files.push(file),
+                    Err(e) => println!("Skipping {}: {e}", path.display())
+                }
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Check by its extension if the file is in one of the supported audio formats
+pub fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| AUDIO_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Read the title, genres and duration of an audio file. If the file has no title tag,
+/// the file name is used instead.
+pub fn read_file(path: &Path) -> Result<LocalFile, String> {
+    let source = File::open(path).map_err(|e| format!("Could not open file: {e}"))?;
+    let stream = MediaSourceStream::new(Box::new(source), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+        hint.with_extension(extension);
+    }
+
+    let mut probed = symphonia::default::get_probe()
+        .format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(|e| format!("Unsupported audio file: {e}"))?;
+
+    // tags can be stored in front of the container (like ID3) or inside of it
+    let mut revisions = vec![];
+    if let Some(revision) = probed.metadata.get().as_ref().and_then(|metadata| metadata.current()) {
+        revisions.push(revision.clone());
+    }
+    if let Some(revision) = probed.format.metadata().current() {
+        revisions.push(revision.clone());
+    }
+
+    let duration = probed.format.default_track().and_then(|track| {
+        let time = track.codec_params.time_base?.calc_time(track.codec_params.n_frames?);
+        Some(Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac))
+    });
+
+    let file_name = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    let title = match (find_tag(&revisions, StandardTagKey::Artist), find_tag(&revisions, StandardTagKey::TrackTitle)) {
+        (Some(artist), Some(title)) => format!("{artist} - {title}"),
+        (None, Some(title)) => title,
+        _ => file_name
+    };
+
+    let tags = find_tag(&revisions, StandardTagKey::Genre)
+        .map(|genres| genres
+            .split([',', ';', '/'])
+            .map(|genre| genre.trim().to_lowercase())
+            .filter(|genre| !genre.is_empty())
+            .collect())
+        .unwrap_or_default();
+
+    Ok(LocalFile {
+        path: path.to_path_buf(),
+        title,
+        tags,
+        duration,
+    })
+}
+
+fn find_tag(revisions: &[MetadataRevision], key: StandardTagKey) -> Option<String> {
+    revisions
+        .iter()
+        .flat_map(|revision| revision.tags())
+        .find(|tag| tag.std_key == Some(key))
+        .map(|tag| tag.value.to_string())
+}
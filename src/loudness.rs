@@ -0,0 +1,332 @@
+use serenity::prelude::Mutex;
+use songbird::tracks::TrackHandle;
+use std::f64::consts::PI;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use tokio::sync::Semaphore;
+use crate::database::{Database, Song};
+use crate::{queue, ytdlp};
+
+/// Loudness all songs are adjusted to in LUFS, the same level YouTube normalizes to
+const TARGET_LOUDNESS: f64 = -14.0;
+
+/// Quiet songs are raised by at most this many dB, so they do not clip
+const MAX_GAIN_DB: f64 = 6.0;
+
+/// Length of the blocks the loudness is measured in
+const BLOCK_SECONDS: f64 = 0.4;
+
+/// Blocks overlap by 75%, so a new block starts every quarter block
+const STEPS_PER_BLOCK: usize = 4;
+
+/// Blocks quieter than this are silence and ignored
+const ABSOLUTE_GATE: f64 = -70.0;
+
+/// Blocks this much quieter than the average are ignored, so quiet passages do not lower the result
+const RELATIVE_GATE: f64 = -10.0;
+
+/// Only one song is analyzed at a time, as YouTube songs have to be downloaded for it
+static ANALYSIS: Semaphore = Semaphore::const_new(1);
+
+/// Measure the loudness of a song in the background and store the gain which normalizes it.
+/// YouTube songs which are not cached are downloaded to a temporary file for this.
+/// If a track is given, its volume is adjusted as soon as the gain is known.
+pub fn analyze_in_background(database: Arc<Mutex<Database>>, song: Song, track: Option<TrackHandle>) {
+    tokio::spawn(async move {
+        let _permit = ANALYSIS.acquire().await;
+
+        let gain = match tokio::task::spawn_blocking(move || analyze(&song).map(|gain| (song, gain))).await {
+            Ok(Ok((song, gain))) => {
+                if let Err(e) = database.lock().await.set_normalization_gain(song.id, gain) {
+                    println!("Could not store normalization gain of {}: {e}", song.title.trim());
+                }
+                gain
+            },
+            Ok(Err(e)) => {
+                println!("Could not measure loudness: {e}");
+                return
+            },
+            Err(e) => {
+                println!("Could not measure loudness: {e:?}");
+                return
+            }
+        };
+
+        if let Some(track) = track {
+            queue::set_normalization(&track, to_factor(gain)).await;
+        }
+    });
+}
+
+/// Convert a gain in dB into a factor of the volume
+pub fn to_factor(gain: f64) -> f32 {
+    10f64.powf(gain / 20.0) as f32
+}
+
+/// Get the gain in dB which brings the song to the target loudness
+fn analyze(song: &Song) -> Result<f64, String> {
+    let loudness = match (&song.file_path, &song.cache, &song.video_id) {
+        (Some(file_path), _, _) => measure(Path::new(file_path))?,
+        (None, Some(cached_file), _) if Path::new(&cached_file.path).exists() => measure(Path::new(&cached_file.path))?,
+        (None, _, Some(video_id)) => {
+            let path = std::env::temp_dir().join(format!("dmbot_loudness_{video_id}.m4a"));
+            ytdlp::download_audio(&song.location(), &path)?;
+            let loudness = measure(&path);
+            let _ = std::fs::remove_file(&path);
+            loudness?
+        },
+        (None, _, None) => unreachable!("the database ensures every song has a video or a file")
+    };
+
+    Ok((TARGET_LOUDNESS - loudness).min(MAX_GAIN_DB))
+}
+
+/// Decode the audio file and measure its integrated loudness in LUFS as described by EBU R128
+fn measure(path: &Path) -> Result<f64, String> {
+    let source = File::open(path).map_err(|e| format!("Could not open file: {e}"))?;
+    let stream = MediaSourceStream::new(Box::new(source), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+        hint.with_extension(extension);
+    }
+
+    let mut format = symphonia::default::get_probe()
+        .format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(|e| format!("Unsupported audio file: {e}"))?
+        .format;
+
+    let track = format.default_track().ok_or("The file contains no audio")?;
+    let track_id = track.id;
+    let sample_rate = track.codec_params.sample_rate.ok_or("The sample rate of the file is unknown")?;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| format!("Unsupported audio codec: {e}"))?;
+
+    let mut meter = Meter::new(sample_rate);
+
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(SymphoniaError::ResetRequired) => break,
+            Err(e) => return Err(format!("Could not read audio: {e}"))
+        };
+
+        if packet.track_id() != track_id {
+            continue
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // damaged packets are skipped
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(e) => return Err(format!("Could not decode audio: {e}"))
+        };
+
+        let channels = decoded.spec().channels.count();
+        let mut samples = SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec());
+        samples.copy_interleaved_ref(decoded);
+        meter.add(samples.samples(), channels);
+    }
+
+    meter.loudness()
+}
+
+/// Measures the integrated loudness of audio which is fed to it in pieces
+struct Meter {
+    sample_rate: u32,
+    filters: Vec<KWeighting>,
+    /// amount of samples per channel in a quarter block
+    step_length: usize,
+    /// the energy of every finished quarter block, summed over all channels
+    steps: Vec<f64>,
+    step_energy: f64,
+    step_samples: usize,
+}
+
+impl Meter {
+    fn new(sample_rate: u32) -> Self {
+        Meter {
+            sample_rate,
+            filters: vec![],
+            step_length: (sample_rate as f64 * BLOCK_SECONDS / STEPS_PER_BLOCK as f64) as usize,
+            steps: vec![],
+            step_energy: 0.0,
+            step_samples: 0,
+        }
+    }
+
+    /// Add samples of the given amount of channels, interleaved like in a [`SampleBuffer`]
+    fn add(&mut self, samples: &[f32], channels: usize) {
+        if self.filters.len() != channels {
+            self.filters = vec![KWeighting::new(self.sample_rate as f64); channels];
+        }
+
+        for frame in samples.chunks_exact(channels) {
+            for (sample, filter) in frame.iter().zip(self.filters.iter_mut()) {
+                let weighted = filter.process(*sample as f64);
+                self.step_energy += weighted * weighted;
+            }
+
+            self.step_samples += 1;
+            if self.step_samples == self.step_length {
+                self.steps.push(self.step_energy);
+                self.step_energy = 0.0;
+                self.step_samples = 0;
+            }
+        }
+    }
+
+    /// The integrated loudness in LUFS of everything added so far
+    fn loudness(&self) -> Result<f64, String> {
+        // the mean square of each block, summed over all channels
+        let blocks = self.steps
+            .windows(STEPS_PER_BLOCK)
+            .map(|window| window.iter().sum::<f64>() / (self.step_length * STEPS_PER_BLOCK) as f64)
+            .filter(|energy| to_loudness(*energy) > ABSOLUTE_GATE)
+            .collect::<Vec<_>>();
+
+        if blocks.is_empty() {
+            return Err("The file is silent or too short".to_string())
+        }
+
+        let threshold = to_loudness(mean(&blocks)) + RELATIVE_GATE;
+        let gated = blocks.into_iter().filter(|energy| to_loudness(*energy) > threshold).collect::<Vec<_>>();
+
+        Ok(to_loudness(mean(&gated)))
+    }
+}
+
+fn to_loudness(energy: f64) -> f64 {
+    -0.691 + 10.0 * energy.log10()
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// The filter EBU R128 applies before measuring, which resembles how loud humans perceive frequencies.
+/// It is a high shelf which boosts high frequencies, followed by a high pass which removes the lowest ones.
+#[derive(Clone, Debug)]
+struct KWeighting {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl KWeighting {
+    /// Calculate the filter coefficients for the given sample rate, as done by libebur128
+    fn new(sample_rate: f64) -> Self {
+        let frequency = 1681.974450955533;
+        let gain = 3.999843853973347;
+        let quality = 0.7071752369554196;
+
+        let k = (PI * frequency / sample_rate).tan();
+        let vh = 10f64.powf(gain / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / quality + k * k;
+
+        let shelf = Biquad::new(
+            [(vh + vb * k / quality + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / quality + k * k) / a0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / quality + k * k) / a0],
+        );
+
+        let frequency = 38.13547087602444;
+        let quality = 0.5003270373238773;
+        let k = (PI * frequency / sample_rate).tan();
+        let a0 = 1.0 + k / quality + k * k;
+
+        let high_pass = Biquad::new(
+            [1.0, -2.0, 1.0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / quality + k * k) / a0],
+        );
+
+        KWeighting { shelf, high_pass }
+    }
+
+    fn process(&mut self, sample: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(sample))
+    }
+}
+
+/// Second order filter in transposed direct form II
+#[derive(Clone, Debug)]
+struct Biquad {
+    b: [f64; 3],
+    /// the feedback coefficients, without the first one which is always 1
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Biquad { b, a, state: [0.0; 2] }
+    }
+
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b[0] * input + self.state[0];
+        self.state[0] = self.b[1] * input - self.a[0] * output + self.state[1];
+        self.state[1] = self.b[2] * input - self.a[1] * output;
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn sine(frequency: f64, amplitude: f64, seconds: f64) -> Vec<f32> {
+        (0..(SAMPLE_RATE as f64 * seconds) as usize)
+            .map(|i| (amplitude * (2.0 * PI * frequency * i as f64 / SAMPLE_RATE as f64).sin()) as f32)
+            .collect()
+    }
+
+    #[test]
+    fn full_scale_sine() {
+        let mut meter = Meter::new(SAMPLE_RATE);
+        meter.add(&sine(997.0, 1.0, 5.0), 1);
+
+        let loudness = meter.loudness().unwrap();
+        assert!((loudness + 3.01).abs() < 0.05, "measured {loudness} LUFS");
+    }
+
+    #[test]
+    fn silent_channel_does_not_count() {
+        let samples = sine(997.0, 1.0, 5.0).into_iter().flat_map(|sample| [sample, 0.0]).collect::<Vec<_>>();
+        let mut meter = Meter::new(SAMPLE_RATE);
+        meter.add(&samples, 2);
+
+        let loudness = meter.loudness().unwrap();
+        assert!((loudness + 3.01).abs() < 0.05, "measured {loudness} LUFS");
+    }
+
+    #[test]
+    fn quiet_passages_are_gated() {
+        let mut samples = sine(997.0, 1.0, 5.0);
+        samples.extend(sine(997.0, 0.001, 5.0));
+        let mut meter = Meter::new(SAMPLE_RATE);
+        meter.add(&samples, 1);
+
+        // without the relative gate this would be about -6 LUFS, the blocks overlapping the change lower it slightly
+        let loudness = meter.loudness().unwrap();
+        assert!((loudness + 3.01).abs() < 0.2, "measured {loudness} LUFS");
+    }
+
+    #[test]
+    fn silence() {
+        let mut meter = Meter::new(SAMPLE_RATE);
+        meter.add(&vec![0.0; SAMPLE_RATE as usize * 5], 1);
+
+        assert_eq!(meter.loudness(), Err("The file is silent or too short".to_string()));
+    }
+}
@@ -1,4 +1,12 @@
+mod ambience;
+mod cache;
 mod database;
+mod fade;
+mod library;
+mod loudness;
+mod queue;
+mod search;
+mod storage;
 mod ytdlp;
 
 use reqwest::Client as HttpClient;
@@ -8,11 +16,18 @@ use serenity::framework::standard::macros::{command, group};
 use serenity::framework::standard::{Args, CommandResult, Configuration};
 use serenity::prelude::*;
 use songbird::input::{Compose, YoutubeDl};
-use songbird::{EventContext, SerenityInit, TrackEvent};
+use songbird::tracks::{PlayMode, TrackQueue};
+use songbird::{Call, EventContext, SerenityInit, TrackEvent};
+use std::collections::{HashMap, HashSet};
 use std::env;
-use std::sync::Arc;
+use std::path::Path;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
 use itertools::Itertools;
-use crate::database::Database;
+use rand::seq::SliceRandom;
+use crate::ambience::Ambience;
+use crate::database::{Database, GuildSettings, LoopMode, Scene, SceneTrack, Song, GLOBAL_LIBRARY};
+use crate::queue::{QueueLoop, QueuedSong};
 use crate::ytdlp::get_video_name;
 // see https://github.com/serenity-rs/serenity/blob/current/examples/e01_basic_ping_bot/src/main.rs
 // see https://github.com/serenity-rs/songbird/blob/current/examples/serenity/voice/src/main.rs
@@ -26,12 +41,24 @@ async fn main() {
 
     let intents = GatewayIntents::non_privileged() | GatewayIntents::MESSAGE_CONTENT;
 
+    let database = Database::open();
+
+    if let Some(directory) = library::get_music_directory() {
+        match library::scan(&directory).and_then(|files| database.index_local_files(&directory, &files)) {
+            Ok((indexed, removed)) => println!("Indexed {indexed} local music files, removed {removed} missing ones"),
+            Err(e) => println!("Could not index local music files: {e}")
+        }
+    }
+
     let mut client = Client::builder(&token, intents)
         .event_handler(Handler)
         .framework(framework)
         .register_songbird()
         .type_map_insert::<HttpKey>(HttpClient::new())
-        .type_map_insert::<DbKey>(Arc::new(Mutex::new(Database::open())))
+        .type_map_insert::<DbKey>(Arc::new(Mutex::new(database)))
+        .type_map_insert::<AmbienceKey>(Arc::new(Mutex::new(Ambience::default())))
+        .type_map_insert::<FollowKey>(Arc::new(Mutex::new(HashMap::new())))
+        .type_map_insert::<CallSetupKey>(Arc::new(Mutex::new(HashSet::new())))
         .await
         .expect("Err creating client");
 
@@ -40,6 +67,51 @@ async fn main() {
     }
 }
 
+/// How long the user has to pick a song when several songs match the input
+const SELECTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Directory next to the executable where songs from attachments are stored
+const SONG_DIRECTORY: &str = "songs";
+
+/// Directory next to the executable where sound effects from attachments are stored
+const SFX_DIRECTORY: &str = "sfx";
+
+/// Volume of ambience tracks in percent if none is given
+const DEFAULT_AMBIENCE_VOLUME: u32 = 50;
+
+/// Maximum length of fading songs in and out
+const MAX_FADE_SECONDS: f64 = 10.0;
+
+/// Amount of characters of the progress bar of the current song
+const PROGRESS_BAR_LENGTH: usize = 20;
+
+/// Environment variable with the minutes the bot stays in a voice channel without anything to do, 0 to stay forever
+const IDLE_MINUTES_VARIABLE: &str = "DMBOT_IDLE_MINUTES";
+
+/// Minutes the bot stays in a voice channel without anything to do if nothing is configured
+const DEFAULT_IDLE_MINUTES: u64 = 10;
+
+/// How often the bot checks whether it has anything to do in a voice channel
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Amount of songs shown on one page of the queue
+const QUEUE_PAGE_SIZE: usize = 10;
+
+/// Amount of entries shown on one page of playlists, tags and sound effects
+const LIST_PAGE_SIZE: usize = 20;
+
+/// Maximum volume of a guild in percent
+const MAX_VOLUME: u32 = 200;
+
+/// Maximum amount of percent a song can be played louder or quieter than the volume of the guild
+const MAX_VOLUME_OFFSET: i32 = 100;
+
+/// Used to show sizes of the audio cache
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
+/// Maximum amount of songs offered to the user to pick from
+const MAX_SELECTION_CANDIDATES: usize = 9;
+
 /// Key to access the client stored in the type map. The client is used to play YouTube tracks.
 struct HttpKey;
 
@@ -53,6 +125,27 @@ impl TypeMapKey for DbKey {
     type Value = Arc<Mutex<Database>>;
 }
 
+/// Key to access the ambience layers of all guilds
+struct AmbienceKey;
+
+impl TypeMapKey for AmbienceKey {
+    type Value = Arc<Mutex<Ambience>>;
+}
+
+/// Key to access the user the bot follows between voice channels, per guild
+struct FollowKey;
+
+impl TypeMapKey for FollowKey {
+    type Value = Arc<Mutex<HashMap<GuildId, UserId>>>;
+}
+
+/// Key to access the guilds whose call already has its event handlers and idle watcher
+struct CallSetupKey;
+
+impl TypeMapKey for CallSetupKey {
+    type Value = Arc<Mutex<HashSet<GuildId>>>;
+}
+
 struct Handler;
 
 #[async_trait]
@@ -61,6 +154,37 @@ impl EventHandler for Handler {
     async fn ready(&self, _ctx: Context, data_about_bot: Ready) {
         println!("{} is connected!", data_about_bot.user.name);
     }
+
+    /// called when someone joins, leaves or moves between voice channels.
+    /// If it is the user the bot follows, the bot moves to their new channel.
+    async fn voice_state_update(&self, context: Context, _old: Option<VoiceState>, new: VoiceState) {
+        let (guild_id, channel_id) = match (new.guild_id, new.channel_id) {
+            (Some(guild_id), Some(channel_id)) => (guild_id, channel_id),
+            _ => return
+        };
+
+        if get_followed_users(&context).await.lock().await.get(&guild_id) != Some(&new.user_id) {
+            return
+        }
+
+        let manager = songbird::get(&context)
+            .await
+            .expect("Songbird Voice client placed in at initialisation.")
+            .clone();
+
+        // only a bot which is in a voice channel follows, it does not join on its own
+        let current_channel = match manager.get(guild_id) {
+            Some(handler_lock) => handler_lock.lock().await.current_channel(),
+            None => None
+        };
+
+        match current_channel {
+            Some(current_channel) if current_channel.0.get() != channel_id.get() => {
+                join_channel(&context, guild_id, channel_id).await;
+            },
+            _ => {}
+        }
+    }
 }
 
 struct TrackErrorNotifier;
@@ -85,229 +209,2486 @@ impl songbird::events::EventHandler for TrackErrorNotifier {
 
 /// All commands the bot supports
 #[group]
-#[commands(play, reg, skip, stop, help)]
+#[commands(join, leave, follow, mute, unmute, deafen, undeafen, play, reg, rescan, alias, tag, playlist, scene, ambience, sfx, cache, fade, volume, loop_mode, queue, np, remove, move_song, shuffle, clear, pause, resume, skip, skipto, stop, help)]
 struct DMBot;
 
-/// Main command which is used to join a channel and play some music from YouTube.
+/// Join the voice channel of the author without playing anything
 #[command]
 #[only_in(guilds)]
-async fn play(
+async fn join(
     context: &Context,
     message: &Message,
-    mut args: Args,
+    _args: Args,
 ) -> CommandResult {
-    let input = args.iter::<String>().map(|r| r.unwrap()).collect::<Vec<_>>().join(" ");
+    if join_voice_channel(context, message).await.is_none() {
+        return Ok(())
+    }
 
-    let (guild_id, channel_id) = get_guild_and_voice_channel(context, message);
-    let connect_to = match channel_id {
-        Some(channel) => channel,
-        None => {
-            check_msg(message.reply(context, "Not in a voice channel").await);
-            return Ok(());
-        }
-    };
+    if let (_, Some(channel_id)) = get_guild_and_voice_channel(context, message) {
+        check_msg(message.channel_id.say(&context.http, format!("Joined {}", channel_id.mention())).await);
+    }
 
-    let manager = songbird::get(&context)
-        .await
-        .expect("Songbird Voice client placed in at initialisation.")
-        .clone();
+    Ok(())
+}
 
-    if let Ok(handler_lock) = manager.join(guild_id, connect_to).await {
-        // Attach an event handler to see notifications of all track errors.
-        let mut handler = handler_lock.lock().await;
-        handler.add_global_event(TrackEvent::Error.into(), TrackErrorNotifier);
+/// Move the bot with the author, or the mentioned user, whenever they switch voice channels.
+/// Use "off" to stop following.
+#[command]
+#[only_in(guilds)]
+async fn follow(
+    context: &Context,
+    message: &Message,
+    args: Args,
+) -> CommandResult {
+    let guild_id = message.guild_id.unwrap();
+    let followed_users = get_followed_users(context).await;
+
+    if args.rest().trim() == "off" {
+        let answer = match followed_users.lock().await.remove(&guild_id) {
+            Some(user_id) => format!("No longer following {}", user_id.mention()),
+            None => "Not following anyone".to_string()
+        };
+
+        check_msg(message.channel_id.say(&context.http, answer).await);
+        return Ok(())
     }
 
-    let http_client = {
-        let data = context.data.read().await;
-        data.get::<HttpKey>()
-            .cloned()
-            .expect("The HTTP client should exist in the type map.")
-    };
+    let user_id = message.mentions.first().map(|user| user.id).unwrap_or(message.author.id);
+    let channel_id = message
+        .guild(&context.cache)
+        .and_then(|guild| guild.voice_states.get(&user_id).and_then(|voice_state| voice_state.channel_id));
 
-    let database = {
-        let data = context.data.read().await;
-        data.get::<DbKey>()
-            .cloned()
-            .expect("The database should exist in the type map")
+    let channel_id = match channel_id {
+        Some(channel_id) => channel_id,
+        None => {
+            check_msg(message.channel_id.say(&context.http, format!("{} is not in a voice channel", user_id.mention())).await);
+            return Ok(())
+        }
     };
 
-    if let Some(handler_lock) = manager.get(guild_id) {
-        let mut handler = handler_lock.lock().await;
-
-        let url = match input.starts_with("https") {
-            true => input,
-            false => {
-                let videos = database.lock().await.find_videos_like(input);
-
-                match videos.len() {
-                    0 => {
-                        check_msg(message.channel_id.say(&context.http, format!("No videos with a name like this exist")).await);
-                        return Ok(())
-                    },
-                    1 => format!("https://www.youtube.com/watch?v={}", videos.get(0).unwrap().clone().0),
-                    _ => {
-                        let videos_string = videos.iter().map(|(_, title)| title).join(", ");
-                        check_msg(message.channel_id.say(&context.http, format!("More than one video was found: {videos_string}. Be more specific")).await);
-                        return Ok(())
-                    }
-                }
-            }
-        };
+    if join_channel(context, guild_id, channel_id).await.is_none() {
+        check_msg(message.channel_id.say(&context.http, "Could not join the voice channel").await);
+        return Ok(())
+    }
 
-        let mut src = YoutubeDl::new(http_client, url);
-        let _ = handler.enqueue_input(src.clone().into()).await;
+    followed_users.lock().await.insert(guild_id, user_id);
 
-        let queue_position = handler.queue().len();
-        let title = src.aux_metadata().await.unwrap().title.unwrap();
+    check_msg(message.channel_id.say(&context.http, format!("Following {} between voice channels", user_id.mention())).await);
+    Ok(())
+}
 
-        check_msg(message.channel_id.say(&context.http, format!("Added '{title}' in queue position {queue_position}")).await);
-    } else {
-        check_msg(
-            message.channel_id
-                .say(&context.http, "Not in a voice channel to play in")
-                .await,
-        );
-    }
+/// Stop the music and the ambience and leave the voice channel
+#[command]
+#[only_in(guilds)]
+async fn leave(
+    context: &Context,
+    message: &Message,
+    _args: Args,
+) -> CommandResult {
+    let answer = match leave_voice_channel(context, message.guild_id.unwrap()).await {
+        true => "Left voice channel",
+        false => "Not in a voice channel"
+    };
 
+    check_msg(message.channel_id.say(&context.http, answer).await);
     Ok(())
 }
 
-/// Used to register a song by storing its YouTube id and name in the dmbot database.
+/// stop sending audio to the voice channel, while the queue keeps going
 #[command]
 #[only_in(guilds)]
-async fn reg(
+async fn mute(
     context: &Context,
     message: &Message,
-    mut args: Args,
+    _args: Args,
 ) -> CommandResult {
-    let url = match args.single::<String>() {
-        Ok(url) => url,
-        Err(_) => {
-            check_msg(
-                message.channel_id
-                    .say(&context.http, "Must provide a URL to a video or audio")
-                    .await,
-            );
+    let manager = songbird::get(context)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
 
-            return Ok(());
+    let handler_lock = match manager.get(message.guild_id.unwrap()) {
+        Some(handler_lock) => handler_lock,
+        None => {
+            check_msg(message.reply(context, "Not in a voice channel").await);
+            return Ok(())
         }
     };
 
-    let title = match get_video_name(&url) {
-        Ok(title) => title,
-        Err(e) => {
-            check_msg(message.channel_id.say(&context.http, format!("Could not retrieve video name. {e}")).await);
-            return Ok(())
+    let mut handler = handler_lock.lock().await;
+
+    let answer = match handler.is_mute() {
+        true => "Already muted".to_string(),
+        false => match handler.mute(true).await {
+            Ok(()) => "Now muted".to_string(),
+            Err(e) => format!("Could not change the voice state. {e:?}")
         }
     };
 
-    let database = {
-        let data = context.data.read().await;
-        data.get::<DbKey>()
-            .cloned()
-            .expect("The database should exist in the type map")
-    };
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// send audio to the voice channel again
+#[command]
+#[only_in(guilds)]
+async fn unmute(
+    context: &Context,
+    message: &Message,
+    _args: Args,
+) -> CommandResult {
+    let manager = songbird::get(context)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
 
-    let raw_id = {
-        let mut id = url.replace("https://www.youtube.com/watch?v=", "");
-        id.split("&").next().unwrap().into()
+    let handler_lock = match manager.get(message.guild_id.unwrap()) {
+        Some(handler_lock) => handler_lock,
+        None => {
+            check_msg(message.reply(context, "Not in a voice channel").await);
+            return Ok(())
+        }
     };
 
-    if let Err(e) = database.lock().await.add_song(raw_id, title) {
-        check_msg(message.channel_id.say(&context.http, format!("Could not store video in database. {e}")).await);
-        return Ok(())
-    }
+    let mut handler = handler_lock.lock().await;
 
-    check_msg(message.channel_id.say(&context.http, "Video registered in database.").await);
+    let answer = match handler.is_mute() {
+        false => "Not muted".to_string(),
+        true => match handler.mute(false).await {
+            Ok(()) => "Unmuted".to_string(),
+            Err(e) => format!("Could not change the voice state. {e:?}")
+        }
+    };
 
+    check_msg(message.channel_id.say(&context.http, answer).await);
     Ok(())
 }
 
-/// stop the current song and go to the next one in the queue
+/// stop receiving audio from the voice channel
 #[command]
 #[only_in(guilds)]
-async fn skip(
+async fn deafen(
     context: &Context,
     message: &Message,
     _args: Args,
 ) -> CommandResult {
-    let guild_id = message.guild_id.unwrap();
-
     let manager = songbird::get(context)
         .await
         .expect("Songbird Voice client placed in at initialisation.")
         .clone();
 
-    if let Some(handler_lock) = manager.get(guild_id) {
-        let handler = handler_lock.lock().await;
-        let queue = handler.queue();
-        let _ = queue.skip();
+    let handler_lock = match manager.get(message.guild_id.unwrap()) {
+        Some(handler_lock) => handler_lock,
+        None => {
+            check_msg(message.reply(context, "Not in a voice channel").await);
+            return Ok(())
+        }
+    };
 
-        let answer = match queue.len() {
-            0 => "Skipping current song. The queue is now empty.",
-            _ => "Skipping current song"
-        }.to_string();
+    let mut handler = handler_lock.lock().await;
 
-        check_msg(message.channel_id.say(
-            &context.http,
-            answer,
-        ).await);
-    }
+    let answer = match handler.is_deaf() {
+        true => "Already deafened".to_string(),
+        false => match handler.deafen(true).await {
+            Ok(()) => "Deafened".to_string(),
+            Err(e) => format!("Could not change the voice state. {e:?}")
+        }
+    };
 
+    check_msg(message.channel_id.say(&context.http, answer).await);
     Ok(())
 }
 
-/// stop the current song and clear the queue
+/// receive audio from the voice channel again
 #[command]
 #[only_in(guilds)]
-async fn stop(
+async fn undeafen(
     context: &Context,
     message: &Message,
     _args: Args,
 ) -> CommandResult {
-    let guild_id = message.guild_id.unwrap();
-
     let manager = songbird::get(context)
         .await
         .expect("Songbird Voice client placed in at initialisation.")
         .clone();
 
-    if let Some(handler_lock) = manager.get(guild_id) {
-        let handler = handler_lock.lock().await;
-        let queue = handler.queue();
-        let _ = queue.stop();
+    let handler_lock = match manager.get(message.guild_id.unwrap()) {
+        Some(handler_lock) => handler_lock,
+        None => {
+            check_msg(message.reply(context, "Not in a voice channel").await);
+            return Ok(())
+        }
+    };
 
-        check_msg(message.channel_id.say(
-            &context.http,
-            "Current song stopped and queue cleared.".to_string(),
-        ).await);
+    let mut handler = handler_lock.lock().await;
+
+    let answer = match handler.is_deaf() {
+        false => "Not deafened".to_string(),
+        true => match handler.deafen(false).await {
+            Ok(()) => "Undeafened".to_string(),
+            Err(e) => format!("Could not change the voice state. {e:?}")
+        }
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// Main command which is used to join a channel and play some music from YouTube.
+#[command]
+#[only_in(guilds)]
+async fn play(
+    context: &Context,
+    message: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let input = args.iter::<String>().map(|r| r.unwrap()).collect::<Vec<_>>().join(" ");
+    let guild_id = message.guild_id.unwrap();
+
+    let handler_lock = match join_voice_channel(context, message).await {
+        Some(handler_lock) => handler_lock,
+        None => return Ok(())
+    };
+
+    let http_client = get_http_client(context).await;
+    let database = get_database(context).await;
+
+    if let Some(tag_input) = input.strip_prefix("tag:") {
+        let (tag, enqueue_all) = match tag_input.split_once(' ') {
+            Some((tag, "all")) => (tag.to_lowercase(), true),
+            _ => (tag_input.trim().to_lowercase(), false)
+        };
+
+        let songs = match database.lock().await.find_songs_with_tag(guild_id.get(), &tag) {
+            Ok(songs) => songs,
+            Err(e) => {
+                check_msg(message.channel_id.say(&context.http, format!("Could not search songs by tag. {e}")).await);
+                return Ok(())
+            }
+        };
+
+        // the songs are returned in random order, so the first one is a random pick
+        let songs = match enqueue_all {
+            true => songs,
+            false => songs.into_iter().take(1).collect()
+        };
+
+        let settings = get_guild_settings(context, guild_id).await;
+        let mut handler = handler_lock.lock().await;
+        enqueue_songs(&mut handler, &http_client, &database, &songs, message.author.id, &settings).await;
+
+        let answer = match songs.len() {
+            0 => format!("No songs are tagged with '{tag}'"),
+            1 => format!("Added '{}' in queue position {}", songs[0].title, handler.queue().len()),
+            amount => format!("Added {amount} songs tagged with '{tag}' to the queue")
+        };
+
+        check_msg(message.channel_id.say(&context.http, answer).await);
+        return Ok(())
+    }
+
+    // registered songs are remembered, so their loudness can be analyzed if this did not happen yet
+    let (song, registered_song) = match storage::is_url(&input) {
+        true => {
+            let metadata = YoutubeDl::new(http_client.clone(), input.clone())
+                .aux_metadata()
+                .await
+                .ok();
+
+            let song = QueuedSong {
+                song_id: None,
+                title: metadata.as_ref().and_then(|metadata| metadata.title.clone()).unwrap_or_else(|| input.clone()),
+                duration: metadata.and_then(|metadata| metadata.duration),
+                location: input,
+                cache: None,
+                requester: message.author.id,
+                gain: 1.0,
+                normalization: 1.0,
+            };
+
+            (song, None)
+        },
+        false => match find_single_song(context, message, &database, guild_id, input).await {
+            Some(song) => {
+                if let Err(e) = database.lock().await.mark_songs_used(std::slice::from_ref(&song)) {
+                    println!("Could not mark song {} as used: {e}", song.id);
+                }
+
+                (QueuedSong::new(&song, message.author.id), Some(song))
+            },
+            None => return Ok(())
+        }
+    };
+
+    let title = song.title.clone();
+    let settings = get_guild_settings(context, guild_id).await;
+    let mut handler = handler_lock.lock().await;
+    let handle = queue::enqueue(&mut handler, &http_client, &database, song, &settings).await;
+
+    if let Some(registered_song) = registered_song.filter(|song| song.normalization_gain.is_none()) {
+        loudness::analyze_in_background(database.clone(), registered_song, Some(handle));
     }
 
+    let queue_position = handler.queue().len();
+
+    check_msg(message.channel_id.say(&context.http, format!("Added '{title}' in queue position {queue_position}")).await);
+
     Ok(())
 }
 
-/// display a help message
+/// Used to register a song by storing its YouTube id and name in the dmbot database.
+/// Instead of a URL, an audio file can be attached, which is downloaded and registered under
+/// the given name or its file name. The song is added to the library of the current guild, or
+/// to the library shared by all guilds if prefixed with "global". An alias can be given with "as <alias>".
 #[command]
 #[only_in(guilds)]
-async fn help(
+async fn reg(
     context: &Context,
     message: &Message,
-    _args: Args,
+    mut args: Args,
 ) -> CommandResult {
-    let mut help_message = String::new();
-    help_message += "!help = show this message";
-    help_message += "\n";
-    help_message += "!play <YouTube URL> = add the given Youtube link to the queue";
-    help_message += "\n";
-    help_message += "!skip = skip the currently playing song and go to the next one in the queue";
-    help_message += "\n";
-    help_message += "!stop = stop the current song and clear the queue";
+    let library = match args.current() {
+        Some("global") => {
+            args.advance();
+            GLOBAL_LIBRARY
+        },
+        _ => message.guild_id.unwrap().get()
+    };
+
+    let mut words = args.rest().split_whitespace().collect::<Vec<_>>();
+    let alias = match words.len() >= 2 && words[words.len() - 2] == "as" {
+        true => {
+            let alias = words.pop().map(str::to_lowercase);
+            words.pop();
+            alias
+        },
+        false => None
+    };
+
+    let database = get_database(context).await;
+
+    let song_id = match message.attachments.first() {
+        Some(attachment) => {
+            if !library::is_audio_file(Path::new(&attachment.filename)) {
+                check_msg(message.channel_id.say(&context.http, "The attachment is no supported audio file").await);
+                return Ok(())
+            }
+
+            let title = match words.is_empty() {
+                true => Path::new(&attachment.filename).file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default(),
+                false => words.join(" ")
+            };
+
+            let path = match storage::download_attachment(&get_http_client(context).await, attachment, SONG_DIRECTORY).await {
+                Ok(path) => path,
+                Err(e) => {
+                    check_msg(message.channel_id.say(&context.http, format!("Could not store audio file. {e}")).await);
+                    return Ok(())
+                }
+            };
+
+            let song_id = match database.lock().await.add_local_song(library, &path.to_string_lossy(), &title) {
+                Ok(song_id) => song_id,
+                Err(e) => {
+                    check_msg(message.channel_id.say(&context.http, format!("Could not store audio file in database. {e}")).await);
+                    return Ok(())
+                }
+            };
+
+            // the file is already on disk, so its loudness can be measured right away
+            let song = Song {
+                id: song_id,
+                video_id: None,
+                file_path: Some(path.to_string_lossy().to_string()),
+                title,
+                cache: None,
+                volume_offset: 0,
+                normalization_gain: None,
+            };
+            loudness::analyze_in_background(database.clone(), song, None);
+
+            song_id
+        },
+        None => {
+            let url = match words.as_slice() {
+                [url] => url.to_string(),
+                _ => {
+                    check_msg(message.channel_id.say(&context.http, "Usage: !reg [global] <URL> [as <alias>], or !reg [global] [name] [as <alias>] with an attached audio file").await);
+                    return Ok(())
+                }
+            };
+
+            let title = match get_video_name(&url) {
+                Ok(title) => title,
+                Err(e) => {
+                    check_msg(message.channel_id.say(&context.http, format!("Could not retrieve video name. {e}")).await);
+                    return Ok(())
+                }
+            };
+
+            let raw_id = {
+                let mut id = url.replace("https://www.youtube.com/watch?v=", "");
+                id.split("&").next().unwrap().into()
+            };
+
+            match database.lock().await.add_song(library, raw_id, title) {
+                Ok(song_id) => song_id,
+                Err(e) => {
+                    check_msg(message.channel_id.say(&context.http, format!("Could not store video in database. {e}")).await);
+                    return Ok(())
+                }
+            }
+        }
+    };
+
+    if let Some(alias) = alias {
+        if let Err(e) = database.lock().await.add_alias(library, &alias, song_id) {
+            check_msg(message.channel_id.say(&context.http, format!("Song registered in database, but the alias could not be stored. {e}")).await);
+            return Ok(())
+        }
+    }
+
+    check_msg(message.channel_id.say(&context.http, "Song registered in database.").await);
 
-    check_msg(message.channel_id.say(&context.http, help_message).await);
     Ok(())
 }
 
+/// Scan the local music directory again and update the local files in the global library
+#[command]
+#[only_in(guilds)]
+async fn rescan(
+    context: &Context,
+    message: &Message,
+    _args: Args,
+) -> CommandResult {
+    let directory = match library::get_music_directory() {
+        Some(directory) => directory,
+        None => {
+            check_msg(message.channel_id.say(&context.http, format!("No local music directory is configured. Set {} to use one.", library::MUSIC_DIRECTORY_VARIABLE)).await);
+            return Ok(())
+        }
+    };
+
+    check_msg(message.channel_id.say(&context.http, "Scanning local music files...").await);
+
+    let scanned_directory = directory.clone();
+    let files = match tokio::task::spawn_blocking(move || library::scan(&scanned_directory)).await? {
+        Ok(files) => files,
+        Err(e) => {
+            check_msg(message.channel_id.say(&context.http, format!("Could not scan local music files. {e}")).await);
+            return Ok(())
+        }
+    };
+
+    let answer = match get_database(context).await.lock().await.index_local_files(&directory, &files) {
+        Ok((indexed, removed)) => format!("Indexed {indexed} local music files, removed {removed} missing ones"),
+        Err(e) => format!("Could not store local music files in database. {e}")
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// Give a registered song a short name which can be used with !play
+#[command]
+#[only_in(guilds)]
+async fn alias(
+    context: &Context,
+    message: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let (alias, song_input) = match (args.single::<String>(), args.remains()) {
+        (Ok(alias), Some(song_input)) => (alias.to_lowercase(), song_input.to_string()),
+        _ => {
+            check_msg(message.channel_id.say(&context.http, "Must provide an alias and a song").await);
+            return Ok(())
+        }
+    };
+
+    let database = get_database(context).await;
+    let guild_id = message.guild_id.unwrap();
+    let song = match find_single_song(context, message, &database, guild_id, song_input).await {
+        Some(song) => song,
+        None => return Ok(())
+    };
+
+    let answer = match database.lock().await.add_alias(guild_id.get(), &alias, song.id) {
+        Ok(_) => format!("'{}' can now be played with '{alias}'", song.title),
+        Err(e) => format!("Could not store alias. {e}")
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// Manage the tags of registered songs, which can be used to play a random song of a kind
+#[command]
+#[only_in(guilds)]
+#[sub_commands(tag_add, tag_remove, tag_list)]
+async fn tag(
+    context: &Context,
+    message: &Message,
+    _args: Args,
+) -> CommandResult {
+    check_msg(message.channel_id.say(&context.http, "Usage: !tag add <tag> <song>, !tag remove <tag> <song> or !tag list [song or page]").await);
+    Ok(())
+}
+
+/// attach a tag to a registered song
+#[command("add")]
+#[only_in(guilds)]
+async fn tag_add(
+    context: &Context,
+    message: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let (tag, song_input) = match (args.single::<String>(), args.remains()) {
+        (Ok(tag), Some(song_input)) => (tag.to_lowercase(), song_input.to_string()),
+        _ => {
+            check_msg(message.channel_id.say(&context.http, "Must provide a tag and a song").await);
+            return Ok(())
+        }
+    };
+
+    let database = get_database(context).await;
+    let song = match find_single_song(context, message, &database, message.guild_id.unwrap(), song_input).await {
+        Some(song) => song,
+        None => return Ok(())
+    };
+
+    let answer = match database.lock().await.add_tag(message.guild_id.unwrap().get(), song.id, &tag) {
+        Ok(_) => format!("Tagged '{}' with '{tag}'", song.title),
+        Err(e) => format!("Could not store tag in database. {e}")
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// remove a tag from a registered song
+#[command("remove")]
+#[only_in(guilds)]
+async fn tag_remove(
+    context: &Context,
+    message: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let (tag, song_input) = match (args.single::<String>(), args.remains()) {
+        (Ok(tag), Some(song_input)) => (tag.to_lowercase(), song_input.to_string()),
+        _ => {
+            check_msg(message.channel_id.say(&context.http, "Must provide a tag and a song").await);
+            return Ok(())
+        }
+    };
+
+    let database = get_database(context).await;
+    let song = match find_single_song(context, message, &database, message.guild_id.unwrap(), song_input).await {
+        Some(song) => song,
+        None => return Ok(())
+    };
+
+    let answer = match database.lock().await.remove_tag(message.guild_id.unwrap().get(), song.id, &tag) {
+        Ok(true) => format!("Removed tag '{tag}' from '{}'", song.title),
+        Ok(false) => format!("'{}' is not tagged with '{tag}'", song.title),
+        Err(e) => format!("Could not remove tag from database. {e}")
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// list the tags of a song, or a page of all tags of the library if no song is given
+#[command("list")]
+#[only_in(guilds)]
+async fn tag_list(
+    context: &Context,
+    message: &Message,
+    args: Args,
+) -> CommandResult {
+    let database = get_database(context).await;
+    let guild_id = message.guild_id.unwrap();
+
+    // a number is the page of all tags, anything else the song to show the tags of
+    let (song_input, page) = match args.remains() {
+        Some(input) => match input.parse::<usize>() {
+            Ok(page) => (None, page.max(1)),
+            Err(_) => (Some(input.to_string()), 1)
+        },
+        None => (None, 1)
+    };
+
+    let (title, lines) = match song_input {
+        Some(song_input) => {
+            let song = match find_single_song(context, message, &database, guild_id, song_input).await {
+                Some(song) => song,
+                None => return Ok(())
+            };
+
+            match database.lock().await.get_tags_of_song(guild_id.get(), song.id) {
+                Ok(tags) if tags.is_empty() => (format!("'{}' has no tags", song.title.trim()), vec![]),
+                Ok(tags) => (format!("Tags of '{}'", song.title.trim()), tags),
+                Err(e) => (format!("Could not load tags. {e}"), vec![])
+            }
+        },
+        None => match database.lock().await.get_tags_of_library(guild_id.get()) {
+            Ok(tags) if tags.is_empty() => ("No songs are tagged yet".to_string(), vec![]),
+            Ok(tags) => ("Tags".to_string(), tags.iter().map(|(tag, amount)| format!("{tag} ({amount})")).collect()),
+            Err(e) => (format!("Could not load tags. {e}"), vec![])
+        }
+    };
+
+    match lines.is_empty() {
+        true => check_msg(message.channel_id.say(&context.http, title).await),
+        false => send_list_page(context, message, &title, &lines, page, "!tag list <page>").await
+    }
+
+    Ok(())
+}
+
+/// Manage named playlists of registered songs. Names containing spaces must be put in quotes.
+#[command]
+#[only_in(guilds)]
+#[sub_commands(playlist_create, playlist_add, playlist_remove, playlist_show, playlist_delete, playlist_play)]
+async fn playlist(
+    context: &Context,
+    message: &Message,
+    _args: Args,
+) -> CommandResult {
+    check_msg(message.channel_id.say(&context.http, "Usage: !playlist create|delete|show|play <name>, !playlist add <name> <song> or !playlist remove <name> <position>").await);
+    Ok(())
+}
+
+/// create a new empty playlist
+#[command("create")]
+#[only_in(guilds)]
+async fn playlist_create(
+    context: &Context,
+    message: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let name = match args.single_quoted::<String>() {
+        Ok(name) => name,
+        Err(_) => {
+            check_msg(message.channel_id.say(&context.http, "Must provide a name for the playlist").await);
+            return Ok(())
+        }
+    };
+
+    let answer = match get_database(context).await.lock().await.create_playlist(message.guild_id.unwrap().get(), &name) {
+        Ok(_) => format!("Created playlist '{name}'"),
+        Err(e) => format!("Could not create playlist. {e}")
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// add a registered song to the end of a playlist
+#[command("add")]
+#[only_in(guilds)]
+async fn playlist_add(
+    context: &Context,
+    message: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let (name, song_input) = match (args.single_quoted::<String>(), args.remains()) {
+        (Ok(name), Some(song_input)) => (name, song_input.to_string()),
+        _ => {
+            check_msg(message.channel_id.say(&context.http, "Must provide a playlist and a song").await);
+            return Ok(())
+        }
+    };
+
+    let database = get_database(context).await;
+    let guild_id = message.guild_id.unwrap();
+    let playlist_id = match find_playlist(context, message, &database, &name).await {
+        Some(playlist_id) => playlist_id,
+        None => return Ok(())
+    };
+
+    let song = match find_single_song(context, message, &database, guild_id, song_input).await {
+        Some(song) => song,
+        None => return Ok(())
+    };
+
+    let answer = match database.lock().await.add_song_to_playlist(playlist_id, song.id) {
+        Ok(position) => format!("Added '{}' to '{name}' at position {position}", song.title),
+        Err(e) => format!("Could not add song to playlist. {e}")
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// remove the song at the given position from a playlist
+#[command("remove")]
+#[only_in(guilds)]
+async fn playlist_remove(
+    context: &Context,
+    message: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let (name, position) = match (args.single_quoted::<String>(), args.single::<usize>()) {
+        (Ok(name), Ok(position)) => (name, position),
+        _ => {
+            check_msg(message.channel_id.say(&context.http, "Must provide a playlist and the position of the song to remove").await);
+            return Ok(())
+        }
+    };
+
+    let database = get_database(context).await;
+    let playlist_id = match find_playlist(context, message, &database, &name).await {
+        Some(playlist_id) => playlist_id,
+        None => return Ok(())
+    };
+
+    let answer = match database.lock().await.remove_song_from_playlist(playlist_id, position) {
+        Ok(true) => format!("Removed song at position {position} from '{name}'"),
+        Ok(false) => format!("'{name}' has no song at position {position}"),
+        Err(e) => format!("Could not remove song from playlist. {e}")
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// show a page of the songs of a playlist, or all playlists if no name is given
+#[command("show")]
+#[only_in(guilds)]
+async fn playlist_show(
+    context: &Context,
+    message: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let database = get_database(context).await;
+
+    let answer = match args.single_quoted::<String>() {
+        Ok(name) => {
+            let page = args.single::<usize>().unwrap_or(1).max(1);
+            let playlist_id = match find_playlist(context, message, &database, &name).await {
+                Some(playlist_id) => playlist_id,
+                None => return Ok(())
+            };
+
+            let songs = database.lock().await.get_playlist_songs(message.guild_id.unwrap().get(), playlist_id);
+            match songs {
+                Ok(songs) if songs.is_empty() => format!("'{name}' is empty"),
+                Ok(songs) => {
+                    let lines = songs.iter().enumerate().map(|(i, song)| format!("{}. {}", i + 1, song.title.trim())).collect::<Vec<_>>();
+                    send_list_page(context, message, &name, &lines, page, &format!("!playlist show \"{name}\" <page>")).await;
+                    return Ok(())
+                },
+                Err(e) => format!("Could not load playlist. {e}")
+            }
+        },
+        Err(_) => match database.lock().await.get_playlists(message.guild_id.unwrap().get()) {
+            Ok(playlists) if playlists.is_empty() => "There are no playlists yet".to_string(),
+            Ok(playlists) => format!("Playlists: {}", playlists.iter().map(|(name, amount)| format!("{name} ({amount})")).join(", ")),
+            Err(e) => format!("Could not load playlists. {e}")
+        }
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// delete a playlist
+#[command("delete")]
+#[only_in(guilds)]
+async fn playlist_delete(
+    context: &Context,
+    message: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let name = match args.single_quoted::<String>() {
+        Ok(name) => name,
+        Err(_) => {
+            check_msg(message.channel_id.say(&context.http, "Must provide the name of the playlist").await);
+            return Ok(())
+        }
+    };
+
+    let answer = match get_database(context).await.lock().await.delete_playlist(message.guild_id.unwrap().get(), &name) {
+        Ok(true) => format!("Deleted playlist '{name}'"),
+        Ok(false) => format!("There is no playlist named '{name}'"),
+        Err(e) => format!("Could not delete playlist. {e}")
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// add all songs of a playlist to the queue, optionally in random order
+#[command("play")]
+#[only_in(guilds)]
+async fn playlist_play(
+    context: &Context,
+    message: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let (name, shuffle) = match (args.single_quoted::<String>(), args.single::<String>()) {
+        (Ok(name), Ok(option)) if option == "shuffle" => (name, true),
+        (Ok(name), Err(_)) => (name, false),
+        _ => {
+            check_msg(message.channel_id.say(&context.http, "Usage: !playlist play <name> [shuffle]").await);
+            return Ok(())
+        }
+    };
+
+    let database = get_database(context).await;
+    let playlist_id = match find_playlist(context, message, &database, &name).await {
+        Some(playlist_id) => playlist_id,
+        None => return Ok(())
+    };
+
+    let mut songs = match database.lock().await.get_playlist_songs(message.guild_id.unwrap().get(), playlist_id) {
+        Ok(songs) => songs,
+        Err(e) => {
+            check_msg(message.channel_id.say(&context.http, format!("Could not load playlist. {e}")).await);
+            return Ok(())
+        }
+    };
+
+    if songs.is_empty() {
+        check_msg(message.channel_id.say(&context.http, format!("'{name}' is empty")).await);
+        return Ok(())
+    }
+
+    if shuffle {
+        songs.shuffle(&mut rand::thread_rng());
+    }
+
+    let handler_lock = match join_voice_channel(context, message).await {
+        Some(handler_lock) => handler_lock,
+        None => return Ok(())
+    };
+
+    let http_client = get_http_client(context).await;
+    let settings = get_guild_settings(context, message.guild_id.unwrap()).await;
+    let mut handler = handler_lock.lock().await;
+    enqueue_songs(&mut handler, &http_client, &database, &songs, message.author.id, &settings).await;
+
+    check_msg(message.channel_id.say(&context.http, format!("Added {} songs of '{name}' to the queue", songs.len())).await);
+    Ok(())
+}
+
+/// Replace the queue with a saved scene, or manage scenes with the sub commands
+#[command]
+#[only_in(guilds)]
+#[sub_commands(scene_save, scene_list, scene_delete)]
+async fn scene(
+    context: &Context,
+    message: &Message,
+    args: Args,
+) -> CommandResult {
+    let name = match args.remains() {
+        Some(name) => name.to_string(),
+        None => {
+            check_msg(message.channel_id.say(&context.http, "Usage: !scene <name>, !scene save <name>, !scene delete <name> or !scene list").await);
+            return Ok(())
+        }
+    };
+
+    let scene = match get_database(context).await.lock().await.get_scene(message.guild_id.unwrap().get(), &name) {
+        Ok(Some(scene)) => scene,
+        Ok(None) => {
+            check_msg(message.channel_id.say(&context.http, format!("There is no scene named '{name}'")).await);
+            return Ok(())
+        },
+        Err(e) => {
+            check_msg(message.channel_id.say(&context.http, format!("Could not load scene. {e}")).await);
+            return Ok(())
+        }
+    };
+
+    let handler_lock = match join_voice_channel(context, message).await {
+        Some(handler_lock) => handler_lock,
+        None => return Ok(())
+    };
+
+    let guild_id = message.guild_id.unwrap();
+    let http_client = get_http_client(context).await;
+    let database = get_database(context).await;
+    let mut settings = get_guild_settings(context, guild_id).await;
+
+    // the queue is replaced, so the settings of the scene only have to apply to the new tracks
+    if let Some(volume) = scene.volume {
+        if let Err(e) = database.lock().await.set_volume(guild_id.get(), volume) {
+            println!("Could not restore volume of scene '{name}': {e}");
+        }
+        settings.volume = volume;
+    }
+
+    if let Some(loop_mode) = scene.loop_mode {
+        if let Err(e) = database.lock().await.set_loop_mode(guild_id.get(), loop_mode) {
+            println!("Could not restore loop mode of scene '{name}': {e}");
+        }
+        settings.loop_mode = loop_mode;
+    }
+
+    // registered songs are queued as usual, songs which were played by URL or deleted since only by their location
+    let songs = {
+        let database = database.lock().await;
+        scene.tracks.into_iter().map(|track| {
+            match track.song_id.map(|song_id| database.get_song(guild_id.get(), song_id)) {
+                Some(Ok(Some(song))) => QueuedSong::new(&song, message.author.id),
+                _ => QueuedSong {
+                    song_id: None,
+                    location: track.location,
+                    title: track.title,
+                    duration: None,
+                    cache: None,
+                    requester: message.author.id,
+                    gain: 1.0,
+                    normalization: 1.0,
+                }
+            }
+        }).collect::<Vec<_>>()
+    };
+
+    let current_queue = handler_lock.lock().await.queue().clone();
+    fade::fade_out_current(&current_queue).await;
+
+    let mut handler = handler_lock.lock().await;
+    handler.queue().stop();
+
+    for song in songs {
+        queue::enqueue(&mut handler, &http_client, &database, song, &settings).await;
+    }
+
+    check_msg(message.channel_id.say(&context.http, format!("Switched to scene '{name}'")).await);
+    Ok(())
+}
+
+/// save the current queue with the volume and loop mode as a scene
+#[command("save")]
+#[only_in(guilds)]
+async fn scene_save(
+    context: &Context,
+    message: &Message,
+    args: Args,
+) -> CommandResult {
+    let name = match args.remains() {
+        Some(name) => name.to_string(),
+        None => {
+            check_msg(message.channel_id.say(&context.http, "Must provide a name for the scene").await);
+            return Ok(())
+        }
+    };
+
+    let guild_id = message.guild_id.unwrap();
+    let manager = songbird::get(context)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    let songs = match manager.get(guild_id) {
+        Some(handler_lock) => queue::get_queued_songs(&*handler_lock.lock().await).await,
+        None => vec![]
+    };
+
+    if songs.is_empty() {
+        check_msg(message.channel_id.say(&context.http, "The queue is empty, there is nothing to save").await);
+        return Ok(())
+    }
+
+    let settings = get_guild_settings(context, guild_id).await;
+    let scene = Scene {
+        tracks: songs.into_iter().map(|song| SceneTrack { location: song.location, title: song.title, song_id: song.song_id }).collect(),
+        volume: Some(settings.volume),
+        loop_mode: Some(settings.loop_mode),
+    };
+
+    let answer = match get_database(context).await.lock().await.save_scene(guild_id.get(), &name, &scene) {
+        Ok(_) => format!("Saved {} songs as scene '{name}'", scene.tracks.len()),
+        Err(e) => format!("Could not save scene. {e}")
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// list all saved scenes
+#[command("list")]
+#[only_in(guilds)]
+async fn scene_list(
+    context: &Context,
+    message: &Message,
+    _args: Args,
+) -> CommandResult {
+    let answer = match get_database(context).await.lock().await.get_scenes(message.guild_id.unwrap().get()) {
+        Ok(scenes) if scenes.is_empty() => "There are no scenes yet".to_string(),
+        Ok(scenes) => format!("Scenes: {}", scenes.join(", ")),
+        Err(e) => format!("Could not load scenes. {e}")
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// delete a saved scene
+#[command("delete")]
+#[only_in(guilds)]
+async fn scene_delete(
+    context: &Context,
+    message: &Message,
+    args: Args,
+) -> CommandResult {
+    let name = match args.remains() {
+        Some(name) => name.to_string(),
+        None => {
+            check_msg(message.channel_id.say(&context.http, "Must provide the name of the scene").await);
+            return Ok(())
+        }
+    };
+
+    let answer = match get_database(context).await.lock().await.delete_scene(message.guild_id.unwrap().get(), &name) {
+        Ok(true) => format!("Deleted scene '{name}'"),
+        Ok(false) => format!("There is no scene named '{name}'"),
+        Err(e) => format!("Could not delete scene. {e}")
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// Play a looping background sound underneath the queue, replacing the current ambience.
+/// The sound can be a registered song or a URL, optionally followed by a volume in percent.
+#[command]
+#[only_in(guilds)]
+#[sub_commands(ambience_add, ambience_clear, ambience_list)]
+async fn ambience(
+    context: &Context,
+    message: &Message,
+    args: Args,
+) -> CommandResult {
+    play_ambience(context, message, args, true).await
+}
+
+/// add another looping background sound to the ambience
+#[command("add")]
+#[only_in(guilds)]
+async fn ambience_add(
+    context: &Context,
+    message: &Message,
+    args: Args,
+) -> CommandResult {
+    play_ambience(context, message, args, false).await
+}
+
+/// stop all background sounds
+#[command("clear")]
+#[only_in(guilds)]
+async fn ambience_clear(
+    context: &Context,
+    message: &Message,
+    _args: Args,
+) -> CommandResult {
+    let stopped = get_ambience(context).await.lock().await.clear(message.guild_id.unwrap());
+
+    let answer = match stopped {
+        0 => "No ambience is playing".to_string(),
+        _ => "Ambience cleared".to_string()
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// list the background sounds which are currently playing
+#[command("list")]
+#[only_in(guilds)]
+async fn ambience_list(
+    context: &Context,
+    message: &Message,
+    _args: Args,
+) -> CommandResult {
+    let ambience = get_ambience(context).await;
+    let ambience = ambience.lock().await;
+    let tracks = ambience.tracks(message.guild_id.unwrap());
+
+    let answer = match tracks.is_empty() {
+        true => "No ambience is playing".to_string(),
+        false => format!("Ambience: {}", tracks.iter().map(|track| format!("{} ({}%)", track.name, (track.volume * 100.0).round())).join(", "))
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+async fn play_ambience(
+    context: &Context,
+    message: &Message,
+    args: Args,
+    replace: bool,
+) -> CommandResult {
+    let mut words = args.rest().split_whitespace().collect::<Vec<_>>();
+    let volume = match words.last().and_then(|word| word.parse::<u32>().ok()) {
+        Some(volume) if words.len() > 1 => {
+            words.pop();
+            volume.min(100)
+        },
+        _ => DEFAULT_AMBIENCE_VOLUME
+    };
+
+    let input = words.join(" ");
+
+    if input.is_empty() {
+        check_msg(message.channel_id.say(&context.http, "Usage: !ambience [add] <song or URL> [volume], !ambience clear or !ambience list").await);
+        return Ok(())
+    }
+
+    let guild_id = message.guild_id.unwrap();
+    let (name, location) = match storage::is_url(&input) {
+        true => (input.clone(), input),
+        false => match find_single_song(context, message, &get_database(context).await, guild_id, input).await {
+            Some(song) => (song.title.trim().to_string(), song.location()),
+            None => return Ok(())
+        }
+    };
+
+    let handler_lock = match join_voice_channel(context, message).await {
+        Some(handler_lock) => handler_lock,
+        None => return Ok(())
+    };
+
+    let http_client = get_http_client(context).await;
+    let ambience = get_ambience(context).await;
+    let mut ambience = ambience.lock().await;
+
+    if replace {
+        ambience.clear(guild_id);
+    }
+
+    let handle = handler_lock.lock().await.play_input(storage::open_input(&http_client, &location));
+    ambience.add(guild_id, name.clone(), volume as f32 / 100.0, handle);
+
+    check_msg(message.channel_id.say(&context.http, format!("Playing '{name}' as ambience at {volume}% volume")).await);
+    Ok(())
+}
+
+/// Play a registered sound effect on top of the music, without touching the queue
+#[command]
+#[only_in(guilds)]
+#[sub_commands(sfx_reg, sfx_remove, sfx_list)]
+async fn sfx(
+    context: &Context,
+    message: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let name = match args.single::<String>() {
+        Ok(name) => name.to_lowercase(),
+        Err(_) => {
+            check_msg(message.channel_id.say(&context.http, "Usage: !sfx <name>, !sfx reg <URL or attachment> <name>, !sfx remove <name> or !sfx list").await);
+            return Ok(())
+        }
+    };
+
+    let source = match get_database(context).await.lock().await.find_sound_effect(message.guild_id.unwrap().get(), &name) {
+        Ok(Some(source)) => source,
+        Ok(None) => {
+            check_msg(message.channel_id.say(&context.http, format!("There is no sound effect named '{name}'")).await);
+            return Ok(())
+        },
+        Err(e) => {
+            check_msg(message.channel_id.say(&context.http, format!("Could not load sound effect. {e}")).await);
+            return Ok(())
+        }
+    };
+
+    let handler_lock = match join_voice_channel(context, message).await {
+        Some(handler_lock) => handler_lock,
+        None => return Ok(())
+    };
+
+    let http_client = get_http_client(context).await;
+    handler_lock.lock().await.play_input(storage::open_input(&http_client, &source));
+
+    Ok(())
+}
+
+/// register a sound effect from a URL or an attached audio file
+#[command("reg")]
+#[only_in(guilds)]
+async fn sfx_reg(
+    context: &Context,
+    message: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let (url, name) = match (message.attachments.first(), args.single::<String>(), args.single::<String>()) {
+        (None, Ok(url), Ok(name)) => (Some(url), name.to_lowercase()),
+        (Some(_), Ok(name), _) => (None, name.to_lowercase()),
+        _ => {
+            check_msg(message.channel_id.say(&context.http, "Usage: !sfx reg <URL> <name>, or !sfx reg <name> with an attached audio file").await);
+            return Ok(())
+        }
+    };
+
+    let source = match url {
+        Some(url) if storage::is_url(&url) => url,
+        Some(url) => {
+            check_msg(message.channel_id.say(&context.http, format!("'{url}' is not a URL, it must start with http:// or https://")).await);
+            return Ok(())
+        },
+        None if !library::is_audio_file(Path::new(&message.attachments[0].filename)) => {
+            check_msg(message.channel_id.say(&context.http, "The attachment is no supported audio file").await);
+            return Ok(())
+        },
+        None => match storage::download_attachment(&get_http_client(context).await, &message.attachments[0], SFX_DIRECTORY).await {
+            Ok(path) => path.display().to_string(),
+            Err(e) => {
+                check_msg(message.channel_id.say(&context.http, format!("Could not store sound effect. {e}")).await);
+                return Ok(())
+            }
+        }
+    };
+
+    let answer = match get_database(context).await.lock().await.add_sound_effect(message.guild_id.unwrap().get(), &name, &source) {
+        Ok(_) => format!("Sound effect '{name}' registered"),
+        Err(e) => format!("Could not store sound effect in database. {e}")
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// remove a sound effect
+#[command("remove")]
+#[only_in(guilds)]
+async fn sfx_remove(
+    context: &Context,
+    message: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let name = match args.single::<String>() {
+        Ok(name) => name.to_lowercase(),
+        Err(_) => {
+            check_msg(message.channel_id.say(&context.http, "Must provide the name of the sound effect").await);
+            return Ok(())
+        }
+    };
+
+    let answer = match get_database(context).await.lock().await.remove_sound_effect(message.guild_id.unwrap().get(), &name) {
+        Ok(Some(source)) => {
+            // downloaded attachments are not needed anymore, URLs are just forgotten
+            if Path::new(&source).is_file() {
+                let _ = std::fs::remove_file(&source);
+            }
+
+            format!("Removed sound effect '{name}'")
+        },
+        Ok(None) => format!("There is no sound effect named '{name}'"),
+        Err(e) => format!("Could not remove sound effect. {e}")
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// list a page of the registered sound effects
+#[command("list")]
+#[only_in(guilds)]
+async fn sfx_list(
+    context: &Context,
+    message: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let page = args.single::<usize>().unwrap_or(1).max(1);
+
+    let sound_effects = get_database(context).await.lock().await.get_sound_effects(message.guild_id.unwrap().get());
+    match sound_effects {
+        Ok(names) if names.is_empty() => check_msg(message.channel_id.say(&context.http, "There are no sound effects yet").await),
+        Ok(names) => send_list_page(context, message, "Sound effects", &names, page, "!sfx list <page>").await,
+        Err(e) => check_msg(message.channel_id.say(&context.http, format!("Could not load sound effects. {e}")).await)
+    }
+
+    Ok(())
+}
+
+/// Download registered YouTube songs, so they are played from disk instead of being streamed.
+/// Use "all" to download every song of the library which is not cached yet. Without arguments,
+/// the cached files are checked for damage and the size of the cache is shown.
+#[command]
+#[only_in(guilds)]
+async fn cache(
+    context: &Context,
+    message: &Message,
+    args: Args,
+) -> CommandResult {
+    let input = args.rest().trim().to_string();
+    let guild_id = message.guild_id.unwrap();
+    let database = get_database(context).await;
+
+    if input.is_empty() {
+        let cached_songs = match database.lock().await.get_cached_songs() {
+            Ok(cached_songs) => cached_songs,
+            Err(e) => {
+                check_msg(message.channel_id.say(&context.http, format!("Could not load cached songs. {e}")).await);
+                return Ok(())
+            }
+        };
+
+        // the checksums are only compared here, as reading every file is too slow whenever a song is played
+        let mut songs = vec![];
+        let mut damaged = 0;
+        for (song_id, cached_file) in cached_songs {
+            if cache::is_intact(&cached_file).await {
+                songs.push(cached_file);
+                continue
+            }
+
+            match cache::remove(&*database.lock().await, song_id, &cached_file) {
+                Ok(_) => damaged += 1,
+                Err(e) => println!("Could not remove damaged cached file {}: {e}", cached_file.path)
+            }
+        }
+
+        let mut answer = format!(
+            "{} songs are cached, using {} of {} MB",
+            songs.len(),
+            songs.iter().map(|cached_file| cached_file.size).sum::<u64>() / BYTES_PER_MB,
+            cache::get_cache_limit() / BYTES_PER_MB
+        );
+
+        if damaged > 0 {
+            answer += &format!(", removed {damaged} damaged or missing files");
+        }
+
+        check_msg(message.channel_id.say(&context.http, answer).await);
+        return Ok(())
+    }
+
+    let songs = match input.as_str() {
+        "all" => match database.lock().await.get_uncached_songs(guild_id.get()) {
+            Ok(songs) => songs,
+            Err(e) => {
+                check_msg(message.channel_id.say(&context.http, format!("Could not load songs. {e}")).await);
+                return Ok(())
+            }
+        },
+        _ => match find_single_song(context, message, &database, guild_id, input).await {
+            Some(song) => vec![song],
+            None => return Ok(())
+        }
+    };
+
+    if songs.len() > 1 {
+        check_msg(message.channel_id.say(&context.http, format!("Downloading {} songs, this may take a while", songs.len())).await);
+    }
+
+    let mut cached = 0;
+    let mut evicted = 0;
+    let mut last_error = None;
+    let mut cached_song_ids = vec![];
+    let mut cached_size = 0;
+    let mut full = false;
+
+    for song in &songs {
+        // songs downloaded by this command were not played yet and would be evicted first for the next one,
+        // so they are kept and no more songs are downloaded once they fill the cache on their own
+        if cached_size >= cache::get_cache_limit() {
+            full = true;
+            break
+        }
+
+        let result = match cache::download(song).await {
+            Ok(cached_file) => {
+                cached_song_ids.push(song.id);
+                cached_size += cached_file.size;
+                let database = database.lock().await;
+                database.set_cached_file(song.id, Some(&cached_file)).and_then(|_| cache::evict(&database, &cached_song_ids))
+            },
+            Err(e) => Err(e)
+        };
+
+        match result {
+            Ok(amount) => {
+                cached += 1;
+                evicted += amount;
+            },
+            Err(e) => {
+                println!("Could not cache {}: {e}", song.title.trim());
+                last_error = Some(e);
+            }
+        }
+    }
+
+    let mut answer = match (songs.as_slice(), last_error) {
+        ([], _) => "All songs are cached already".to_string(),
+        ([song], None) => format!("Cached '{}'", song.title.trim()),
+        ([song], Some(e)) => format!("Could not cache '{}'. {e}", song.title.trim()),
+        _ => format!("Cached {cached} of {} songs", songs.len())
+    };
+
+    if full {
+        answer += ", stopped as the cache is full";
+    }
+
+    if evicted > 0 {
+        answer += &format!(", removed {evicted} least recently played songs to stay within the size limit");
+    }
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// Set how many seconds songs fade in and out when they start, end, are skipped or replaced
+#[command]
+#[only_in(guilds)]
+async fn fade(
+    context: &Context,
+    message: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let seconds = match args.single::<f64>() {
+        Ok(seconds) if (0.0..=MAX_FADE_SECONDS).contains(&seconds) => seconds,
+        _ => {
+            check_msg(message.channel_id.say(&context.http, format!("Must provide the fade length in seconds, between 0 and {MAX_FADE_SECONDS}")).await);
+            return Ok(())
+        }
+    };
+
+    let guild_id = message.guild_id.unwrap();
+    let length = Duration::from_secs_f64(seconds);
+
+    if let Err(e) = get_database(context).await.lock().await.set_fade(guild_id.get(), length) {
+        check_msg(message.channel_id.say(&context.http, format!("Could not store fade length. {e}")).await);
+        return Ok(())
+    }
+
+    let manager = songbird::get(context)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let tracks = handler_lock.lock().await.queue().current_queue();
+        fade::set_length(&tracks, length).await;
+    }
+
+    let answer = match length.is_zero() {
+        true => "Songs no longer fade in and out".to_string(),
+        false => format!("Songs now fade in and out over {seconds} seconds")
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// Set the volume of the current and all following songs in percent. Without arguments, the current volume is shown.
+#[command]
+#[only_in(guilds)]
+#[sub_commands(volume_song)]
+async fn volume(
+    context: &Context,
+    message: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let guild_id = message.guild_id.unwrap();
+
+    if args.is_empty() {
+        let volume = get_guild_settings(context, guild_id).await.volume;
+        check_msg(message.channel_id.say(&context.http, format!("Volume is {volume}%. Usage: !volume <0-{MAX_VOLUME}>")).await);
+        return Ok(())
+    }
+
+    let volume = match args.single::<u32>() {
+        Ok(volume) if volume <= MAX_VOLUME => volume,
+        _ => {
+            check_msg(message.channel_id.say(&context.http, format!("Must provide the volume in percent, between 0 and {MAX_VOLUME}")).await);
+            return Ok(())
+        }
+    };
+
+    if let Err(e) = get_database(context).await.lock().await.set_volume(guild_id.get(), volume) {
+        check_msg(message.channel_id.say(&context.http, format!("Could not store volume. {e}")).await);
+        return Ok(())
+    }
+
+    let manager = songbird::get(context)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let tracks = handler_lock.lock().await.queue().current_queue();
+        fade::set_volume(&tracks, volume as f32 / 100.0).await;
+    }
+
+    check_msg(message.channel_id.say(&context.http, format!("Volume set to {volume}%")).await);
+    Ok(())
+}
+
+/// play a registered song louder or quieter than the volume of the guild
+#[command("song")]
+#[only_in(guilds)]
+async fn volume_song(
+    context: &Context,
+    message: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let (volume_offset, song_input) = match (args.single::<i32>(), args.remains()) {
+        (Ok(volume_offset), Some(song_input)) if (-MAX_VOLUME_OFFSET..=MAX_VOLUME_OFFSET).contains(&volume_offset) => (volume_offset, song_input.to_string()),
+        _ => {
+            check_msg(message.channel_id.say(&context.http, format!("Must provide an offset in percent between -{MAX_VOLUME_OFFSET} and {MAX_VOLUME_OFFSET} and a song")).await);
+            return Ok(())
+        }
+    };
+
+    let database = get_database(context).await;
+    let song = match find_single_song(context, message, &database, message.guild_id.unwrap(), song_input).await {
+        Some(song) => song,
+        None => return Ok(())
+    };
+
+    let answer = match database.lock().await.set_song_volume_offset(message.guild_id.unwrap().get(), song.id, volume_offset) {
+        Ok(_) if volume_offset == 0 => format!("'{}' is now played at the volume of the server", song.title.trim()),
+        Ok(_) => format!("'{}' is now played {}% {}", song.title.trim(), volume_offset.abs(), if volume_offset > 0 { "louder" } else { "quieter" }),
+        Err(e) => format!("Could not store volume of the song. {e}")
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// Set whether the current track or the whole queue repeats. Without arguments, the current mode is shown.
+#[command("loop")]
+#[only_in(guilds)]
+async fn loop_mode(
+    context: &Context,
+    message: &Message,
+    args: Args,
+) -> CommandResult {
+    let guild_id = message.guild_id.unwrap();
+
+    let loop_mode = match args.rest().trim() {
+        "" => {
+            let loop_mode = get_guild_settings(context, guild_id).await.loop_mode;
+            check_msg(message.channel_id.say(&context.http, format!("Loop mode is {}. Usage: !loop track|queue|off", loop_mode.name())).await);
+            return Ok(())
+        },
+        input => match input.parse::<LoopMode>() {
+            Ok(loop_mode) => loop_mode,
+            Err(e) => {
+                check_msg(message.channel_id.say(&context.http, e).await);
+                return Ok(())
+            }
+        }
+    };
+
+    if let Err(e) = get_database(context).await.lock().await.set_loop_mode(guild_id.get(), loop_mode) {
+        check_msg(message.channel_id.say(&context.http, format!("Could not store loop mode. {e}")).await);
+        return Ok(())
+    }
+
+    let manager = songbird::get(context)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        queue::set_loop_mode(handler_lock.lock().await.queue(), loop_mode);
+    }
+
+    let answer = match loop_mode {
+        LoopMode::Off => "Songs no longer repeat",
+        LoopMode::Track => "The current song now repeats",
+        LoopMode::Queue => "The queue now repeats, finished songs are added to its end again"
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// Show the current and upcoming songs with their length and who requested them.
+/// Long queues are split into pages, the page to show can be given as argument.
+#[command]
+#[only_in(guilds)]
+async fn queue(
+    context: &Context,
+    message: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let guild_id = message.guild_id.unwrap();
+    let page = args.single::<usize>().unwrap_or(1).max(1);
+
+    let manager = songbird::get(context)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    let (songs, position) = match manager.get(guild_id) {
+        Some(handler_lock) => {
+            let handler = handler_lock.lock().await;
+            let position = match handler.queue().current() {
+                Some(current) => current.get_info().await.map(|state| state.position).unwrap_or_default(),
+                None => Duration::ZERO
+            };
+
+            (queue::get_queued_songs(&handler).await, position)
+        },
+        None => (vec![], Duration::ZERO)
+    };
+
+    if songs.is_empty() {
+        check_msg(message.channel_id.say(&context.http, "The queue is empty").await);
+        return Ok(())
+    }
+
+    let pages = songs.len().div_ceil(QUEUE_PAGE_SIZE);
+    if page > pages {
+        check_msg(message.channel_id.say(&context.http, format!("The queue only has {pages} pages")).await);
+        return Ok(())
+    }
+
+    let lines = songs
+        .iter()
+        .enumerate()
+        .skip((page - 1) * QUEUE_PAGE_SIZE)
+        .take(QUEUE_PAGE_SIZE)
+        .map(|(i, song)| {
+            let length = song.duration.map(queue::format_duration).unwrap_or_else(|| "?:??".to_string());
+
+            match i {
+                0 => format!("**{}. {}** [{}/{length}] - {} (now playing)", i + 1, song.title, queue::format_duration(position), song.requester.mention()),
+                _ => format!("{}. {} [{length}] - {}", i + 1, song.title, song.requester.mention())
+            }
+        })
+        .join("\n");
+
+    let remaining = songs.iter().filter_map(|song| song.duration).sum::<Duration>().saturating_sub(position);
+    let unknown = songs.iter().filter(|song| song.duration.is_none()).count();
+
+    let mut footer = format!("Page {page} of {pages} | {} songs | {} remaining", songs.len(), queue::format_duration(remaining));
+    if unknown > 0 {
+        footer += &format!(" plus {unknown} songs of unknown length");
+    }
+    if pages > 1 {
+        footer += " | !queue <page> to see more";
+    }
+
+    let embed = CreateEmbed::new()
+        .title("Queue")
+        .description(lines)
+        .footer(CreateEmbedFooter::new(footer));
+
+    check_msg(message.channel_id.send_message(&context.http, CreateMessage::new().embed(embed)).await);
+    Ok(())
+}
+
+/// Show the song which is playing right now, who requested it and how far it got
+#[command]
+#[only_in(guilds)]
+async fn np(
+    context: &Context,
+    message: &Message,
+    _args: Args,
+) -> CommandResult {
+    let guild_id = message.guild_id.unwrap();
+
+    let manager = songbird::get(context)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    let current = match manager.get(guild_id) {
+        Some(handler_lock) => handler_lock.lock().await.queue().current(),
+        None => None
+    };
+
+    let (song, state) = match current {
+        Some(current) => match (queue::get_queued_song(&current).await, current.get_info().await) {
+            (Some(song), Ok(state)) => (song, state),
+            _ => {
+                check_msg(message.channel_id.say(&context.http, "Could not read the current song").await);
+                return Ok(())
+            }
+        },
+        None => {
+            check_msg(message.channel_id.say(&context.http, "Nothing is playing right now").await);
+            return Ok(())
+        }
+    };
+
+    let time = match song.duration {
+        Some(duration) => format!(
+            "{} {} / {}",
+            progress_bar(state.position, duration),
+            queue::format_duration(state.position),
+            queue::format_duration(duration)
+        ),
+        None => format!("{} / ?:??", queue::format_duration(state.position))
+    };
+
+    let settings = get_guild_settings(context, guild_id).await;
+    let volume = match ((song.gain - 1.0) * 100.0).round() as i32 {
+        0 => format!("{}%", settings.volume),
+        offset => format!("{}% ({offset:+}% for this song)", settings.volume)
+    };
+
+    let title = match state.playing {
+        PlayMode::Pause => "Paused",
+        _ => "Now playing"
+    };
+
+    let embed = CreateEmbed::new()
+        .title(title)
+        .description(format!("**{}**\n{time}", song.title))
+        .field("Requested by", song.requester.mention().to_string(), true)
+        .field("Loop", settings.loop_mode.name(), true)
+        .field("Volume", volume, true);
+
+    check_msg(message.channel_id.send_message(&context.http, CreateMessage::new().embed(embed)).await);
+    Ok(())
+}
+
+/// Draw how far the song got as a line with a marker at the current position
+fn progress_bar(position: Duration, duration: Duration) -> String {
+    let progress = match duration.is_zero() {
+        true => 0.0,
+        false => (position.as_secs_f64() / duration.as_secs_f64()).min(1.0)
+    };
+    let marker = ((progress * PROGRESS_BAR_LENGTH as f64) as usize).min(PROGRESS_BAR_LENGTH - 1);
+
+    (0..PROGRESS_BAR_LENGTH).map(|i| if i == marker { '🔘' } else { '▬' }).collect()
+}
+
+/// Pause the current song, the ambience keeps playing
+#[command]
+#[only_in(guilds)]
+async fn pause(
+    context: &Context,
+    message: &Message,
+    _args: Args,
+) -> CommandResult {
+    let queue = match get_queue(context, message).await {
+        Some(queue) => queue,
+        None => return Ok(())
+    };
+
+    let answer = match queue.current() {
+        Some(current) => match current.get_info().await {
+            Ok(state) if state.playing == PlayMode::Pause => "Already paused".to_string(),
+            Ok(_) => match current.pause() {
+                Ok(()) => "Paused the current song, use !resume to continue".to_string(),
+                Err(e) => format!("Could not pause the current song. {e}")
+            },
+            Err(e) => format!("Could not read the current song. {e}")
+        },
+        None => "Nothing is playing right now".to_string()
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// Continue playing the paused song
+#[command]
+#[only_in(guilds)]
+async fn resume(
+    context: &Context,
+    message: &Message,
+    _args: Args,
+) -> CommandResult {
+    let queue = match get_queue(context, message).await {
+        Some(queue) => queue,
+        None => return Ok(())
+    };
+
+    let answer = match queue.current() {
+        Some(current) => match current.get_info().await {
+            Ok(state) if state.playing == PlayMode::Play => "Already playing".to_string(),
+            Ok(_) => match current.play() {
+                Ok(()) => "Resumed the current song".to_string(),
+                Err(e) => format!("Could not resume the current song. {e}")
+            },
+            Err(e) => format!("Could not read the current song. {e}")
+        },
+        None => "Nothing is playing right now".to_string()
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// stop the current song and go to the next one in the queue
+#[command]
+#[only_in(guilds)]
+async fn skip(
+    context: &Context,
+    message: &Message,
+    _args: Args,
+) -> CommandResult {
+    let guild_id = message.guild_id.unwrap();
+
+    let manager = songbird::get(context)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+        let queue = handler.queue();
+        let remaining = queue.len().saturating_sub(1);
+        fade::skip(queue).await;
+
+        let answer = match remaining {
+            0 => "Skipping current song. The queue is now empty.",
+            _ => "Skipping current song"
+        }.to_string();
+
+        check_msg(message.channel_id.say(
+            &context.http,
+            answer,
+        ).await);
+    }
+
+    Ok(())
+}
+
+/// Remove the song at the given position from the queue
+#[command]
+#[only_in(guilds)]
+async fn remove(
+    context: &Context,
+    message: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let queue = match get_queue(context, message).await {
+        Some(queue) => queue,
+        None => return Ok(())
+    };
+
+    let answer = match parse_queue_position(&mut args, &queue).map(|index| queue.dequeue(index)) {
+        Ok(Some(removed)) => {
+            let _ = removed.stop();
+            let title = queue::get_queued_song(&removed).await.map(|song| song.title).unwrap_or_default();
+            format!("Removed '{title}' from the queue")
+        },
+        Ok(None) => "The song is no longer in the queue".to_string(),
+        Err(e) => e
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// Move the song at the first position of the queue to the second position
+#[command("move")]
+#[only_in(guilds)]
+async fn move_song(
+    context: &Context,
+    message: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let queue = match get_queue(context, message).await {
+        Some(queue) => queue,
+        None => return Ok(())
+    };
+
+    let (from, to) = match (parse_queue_position(&mut args, &queue), parse_queue_position(&mut args, &queue)) {
+        (Ok(from), Ok(to)) => (from, to),
+        (Err(e), _) | (_, Err(e)) => {
+            check_msg(message.channel_id.say(&context.http, format!("{e}. Usage: !move <from> <to>")).await);
+            return Ok(())
+        }
+    };
+
+    let moved = queue.modify_queue(|tracks| {
+        let track = tracks.remove(from)?;
+        let handle = track.handle();
+        tracks.insert(to.min(tracks.len()), track);
+        Some(handle)
+    });
+
+    let answer = match moved {
+        Some(handle) => {
+            let title = queue::get_queued_song(&handle).await.map(|song| song.title).unwrap_or_default();
+            format!("Moved '{title}' to position {}", to + 1)
+        },
+        None => "The song is no longer in the queue".to_string()
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// Shuffle the upcoming songs, the current song keeps playing
+#[command]
+#[only_in(guilds)]
+async fn shuffle(
+    context: &Context,
+    message: &Message,
+    _args: Args,
+) -> CommandResult {
+    let queue = match get_queue(context, message).await {
+        Some(queue) => queue,
+        None => return Ok(())
+    };
+
+    let shuffled = queue.modify_queue(|tracks| match tracks.make_contiguous().split_first_mut() {
+        Some((_, upcoming)) => {
+            upcoming.shuffle(&mut rand::thread_rng());
+            upcoming.len()
+        },
+        None => 0
+    });
+
+    let answer = match shuffled {
+        0 | 1 => "There are not enough upcoming songs to shuffle".to_string(),
+        amount => format!("Shuffled {amount} upcoming songs")
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// Remove all upcoming songs from the queue, the current song keeps playing
+#[command]
+#[only_in(guilds)]
+async fn clear(
+    context: &Context,
+    message: &Message,
+    _args: Args,
+) -> CommandResult {
+    let queue = match get_queue(context, message).await {
+        Some(queue) => queue,
+        None => return Ok(())
+    };
+
+    let removed = queue.modify_queue(|tracks| tracks.drain(1.min(tracks.len())..).collect::<Vec<_>>());
+
+    for track in &removed {
+        let _ = track.stop();
+    }
+
+    let answer = match removed.len() {
+        0 => "There are no upcoming songs".to_string(),
+        amount => format!("Removed {amount} upcoming songs from the queue")
+    };
+
+    check_msg(message.channel_id.say(&context.http, answer).await);
+    Ok(())
+}
+
+/// Skip the current song and all songs up to the given position of the queue
+#[command]
+#[only_in(guilds)]
+async fn skipto(
+    context: &Context,
+    message: &Message,
+    mut args: Args,
+) -> CommandResult {
+    let queue = match get_queue(context, message).await {
+        Some(queue) => queue,
+        None => return Ok(())
+    };
+
+    let index = match parse_queue_position(&mut args, &queue) {
+        Ok(index) => index,
+        Err(e) => {
+            check_msg(message.channel_id.say(&context.http, e).await);
+            return Ok(())
+        }
+    };
+
+    // the songs in between are removed, so the wanted song follows right after the current one
+    let removed = queue.modify_queue(|tracks| tracks.drain(1..index.min(tracks.len())).collect::<Vec<_>>());
+
+    for track in &removed {
+        let _ = track.stop();
+    }
+
+    let title = match queue.current_queue().get(1) {
+        Some(handle) => queue::get_queued_song(handle).await.map(|song| song.title).unwrap_or_default(),
+        None => {
+            check_msg(message.channel_id.say(&context.http, "The song is no longer in the queue").await);
+            return Ok(())
+        }
+    };
+
+    fade::skip(&queue).await;
+
+    check_msg(message.channel_id.say(&context.http, format!("Skipping to '{title}'")).await);
+    Ok(())
+}
+
+/// stop the current song and clear the queue
+#[command]
+#[only_in(guilds)]
+async fn stop(
+    context: &Context,
+    message: &Message,
+    _args: Args,
+) -> CommandResult {
+    let guild_id = message.guild_id.unwrap();
+
+    let manager = songbird::get(context)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let queue = handler_lock.lock().await.queue().clone();
+        fade::fade_out_current(&queue).await;
+        queue.stop();
+
+        check_msg(message.channel_id.say(
+            &context.http,
+            "Current song stopped and queue cleared.".to_string(),
+        ).await);
+    }
+
+    Ok(())
+}
+
+/// display a help message
+#[command]
+#[only_in(guilds)]
+async fn help(
+    context: &Context,
+    message: &Message,
+    _args: Args,
+) -> CommandResult {
+    // a message may only contain 2000 characters, an embed 6000 with at most 1024 per field, so the commands are grouped into fields
+    let embed = CreateEmbed::new()
+        .title("Commands")
+        .field("General", [
+            "!help = show this message",
+            "!join / !leave = join your voice channel or leave the current one, stopping all music",
+            "!follow [@user] / !follow off = move with yourself (or the given user) between voice channels, or stop following",
+            "!mute / !unmute = stop or continue sending audio to the voice channel",
+            "!deafen / !undeafen = stop or continue receiving audio from the voice channel",
+        ].join("\n"), false)
+        .field("Library", [
+            "!play <YouTube URL> = add the given Youtube link to the queue",
+            "!reg [global] <YouTube URL> [as <alias>] = register a song in the library of this server (or the library shared by all servers)",
+            "!reg [global] [name] [as <alias>] with an attached audio file = register the file as a song",
+            "!rescan = update the library with the files in the local music directory",
+            "!alias <alias> <song> = give a registered song a short name to play it with",
+            "!play tag:<tag> [all] = add a random song with the given tag (or all of them) to the queue",
+            "!tag add|remove <tag> <song> = attach a tag to a registered song or remove it",
+            "!tag list [song or page] = show the tags of a song or all tags in use",
+            "!cache <song> / !cache all = download a registered YouTube song (or all of them) to play it without streaming",
+            "!cache = check the downloaded songs for damage and show how much space they use",
+        ].join("\n"), false)
+        .field("Playlists and scenes", [
+            "!playlist create|delete|show <name> = manage playlists, use quotes for names with spaces. Add a page number to show more songs",
+            "!playlist add <name> <song> / !playlist remove <name> <position> = change the songs of a playlist",
+            "!playlist play <name> [shuffle] = add all songs of a playlist to the queue",
+            "!scene <name> = replace the queue with a saved scene",
+            "!scene save|delete <name> / !scene list = save the current queue with its volume and loop mode as a scene, delete a scene or list all scenes",
+        ].join("\n"), false)
+        .field("Playback", [
+            "!volume <0-200> = set the volume in percent of the current and all following songs",
+            "!volume song <-100 to 100> <song> = play a registered song louder or quieter than the others, in percent",
+            "!loop track|queue|off = repeat the current song or the whole queue, or stop repeating",
+            "!fade <seconds> = set how long songs fade in and out, 0 to disable fading",
+            "!queue [page] = show the songs in the queue, who requested them and how long they take",
+            "!np = show the song which is playing right now and how far it got",
+            "!pause / !resume = pause the current song or continue playing it",
+            "!skip = skip the currently playing song and go to the next one in the queue",
+            "!stop = stop the current song and clear the queue",
+            "!skipto <position> = skip to the song at the given position of the queue",
+            "!remove <position> / !move <from> <to> = remove an upcoming song or change its position in the queue",
+            "!shuffle / !clear = shuffle or remove the upcoming songs, the current song keeps playing",
+        ].join("\n"), false)
+        .field("Ambience and sound effects", [
+            "!ambience [add] <song> [volume] = loop a background sound underneath the music, replacing the current ambience unless 'add' is used",
+            "!ambience clear|list = stop or show the background sounds",
+            "!sfx <name> = play a sound effect on top of the music",
+            "!sfx reg <URL or attachment> <name> / !sfx remove <name> / !sfx list [page] = manage sound effects",
+        ].join("\n"), false);
+
+    check_msg(message.channel_id.send_message(&context.http, CreateMessage::new().embed(embed)).await);
+    Ok(())
+}
+
+/// Get the queue of the guild of the message. If the bot is not in a call there, the user is told so.
+async fn get_queue(context: &Context, message: &Message) -> Option<TrackQueue> {
+    let manager = songbird::get(context)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    match manager.get(message.guild_id.unwrap()) {
+        Some(handler_lock) => Some(handler_lock.lock().await.queue().clone()),
+        None => {
+            check_msg(message.channel_id.say(&context.http, "Not in a voice channel to play in").await);
+            None
+        }
+    }
+}
+
+/// Read an upcoming position of the queue from the arguments and return its index.
+/// Position 1 is the current song, which can not be picked.
+fn parse_queue_position(args: &mut Args, queue: &TrackQueue) -> Result<usize, String> {
+    let position = args.single::<usize>().map_err(|_| "Must provide a position in the queue".to_string())?;
+
+    match position {
+        0 => Err("Positions start at 1".to_string()),
+        1 => Err("Position 1 is the current song, use !skip or !stop instead".to_string()),
+        _ if position > queue.len() => Err(format!("The queue only has {} songs", queue.len())),
+        _ => Ok(position - 1)
+    }
+}
+
+/// Get the HTTP client from the type map of the context
+async fn get_http_client(context: &Context) -> HttpClient {
+    let data = context.data.read().await;
+    data.get::<HttpKey>()
+        .cloned()
+        .expect("The HTTP client should exist in the type map.")
+}
+
+/// Get the settings of the given guild. If they can not be loaded, the default settings are used.
+async fn get_guild_settings(context: &Context, guild_id: GuildId) -> GuildSettings {
+    match get_database(context).await.lock().await.get_guild_settings(guild_id.get()) {
+        Ok(settings) => settings,
+        Err(e) => {
+            println!("Could not load settings of guild {guild_id}: {e}");
+            GuildSettings::default()
+        }
+    }
+}
+
+/// Get the ambience layers from the type map of the context
+async fn get_ambience(context: &Context) -> Arc<Mutex<Ambience>> {
+    let data = context.data.read().await;
+    data.get::<AmbienceKey>()
+        .cloned()
+        .expect("The ambience should exist in the type map")
+}
+
+/// Get the users the bot follows from the type map of the context
+async fn get_followed_users(context: &Context) -> Arc<Mutex<HashMap<GuildId, UserId>>> {
+    let data = context.data.read().await;
+    data.get::<FollowKey>()
+        .cloned()
+        .expect("The followed users should exist in the type map")
+}
+
+/// Get the guilds whose call is set up from the type map of the context
+async fn get_set_up_calls(context: &Context) -> Arc<Mutex<HashSet<GuildId>>> {
+    let data = context.data.read().await;
+    data.get::<CallSetupKey>()
+        .cloned()
+        .expect("The set up calls should exist in the type map")
+}
+
+/// Get the database from the type map of the context
+async fn get_database(context: &Context) -> Arc<Mutex<Database>> {
+    let data = context.data.read().await;
+    data.get::<DbKey>()
+        .cloned()
+        .expect("The database should exist in the type map")
+}
+
+/// Find the registered song which matches the given input best. If no song matches, the user is
+/// told so and None is returned. If several songs match about equally well, the user is asked to
+/// pick one of them by replying with its number.
+async fn find_single_song(
+    context: &Context,
+    message: &Message,
+    database: &Arc<Mutex<Database>>,
+    guild_id: GuildId,
+    input: String,
+) -> Option<Song> {
+    let videos = database.lock().await.find_videos_like(guild_id.get(), input);
+
+    match videos.as_slice() {
+        [] => {
+            check_msg(message.channel_id.say(&context.http, "No videos with a name like this exist").await);
+            None
+        },
+        [(song, _)] => Some(song.clone()),
+        [(best, best_score), (_, second_score), ..] if *best_score >= 1.0 || best_score - second_score >= search::CLEAR_WIN_MARGIN => Some(best.clone()),
+        [(_, best_score), ..] => {
+            let candidates = videos
+                .iter()
+                .take_while(|(_, score)| best_score - score < search::CLEAR_WIN_MARGIN)
+                .take(MAX_SELECTION_CANDIDATES)
+                .map(|(song, _)| song.clone())
+                .collect::<Vec<_>>();
+
+            select_song(context, message, candidates).await
+        }
+    }
+}
+
+/// Let the author of the message pick one of the given songs by replying with its number
+async fn select_song(
+    context: &Context,
+    message: &Message,
+    candidates: Vec<Song>,
+) -> Option<Song> {
+    let candidates_string = candidates
+        .iter()
+        .enumerate()
+        .map(|(i, song)| format!("{}. {}", i + 1, song.title.trim()))
+        .join("\n");
+
+    check_msg(message.channel_id.say(
+        &context.http,
+        format!("More than one video was found. Reply with the number of the one you want:\n{candidates_string}"),
+    ).await);
+
+    let reply = message.channel_id
+        .await_reply(context.shard.clone())
+        .author_id(message.author.id)
+        .timeout(SELECTION_TIMEOUT)
+        .await;
+
+    let reply = match reply {
+        Some(reply) => reply,
+        None => {
+            check_msg(message.channel_id.say(&context.http, "No video was selected in time").await);
+            return None
+        }
+    };
+
+    match reply.content.trim().parse::<usize>() {
+        Ok(number) if (1..=candidates.len()).contains(&number) => candidates.into_iter().nth(number - 1),
+        _ => {
+            check_msg(message.channel_id.say(&context.http, "That is not one of the listed numbers, no video was selected").await);
+            None
+        }
+    }
+}
+
+/// Join the voice channel of the author of the message, or stay in it if the bot is already there,
+/// and return the call of the guild. If the author is in no voice channel, they are told so.
+async fn join_voice_channel(context: &Context, message: &Message) -> Option<Arc<Mutex<Call>>> {
+    let (guild_id, channel_id) = get_guild_and_voice_channel(context, message);
+    let connect_to = match channel_id {
+        Some(channel) => channel,
+        None => {
+            check_msg(message.reply(context, "Not in a voice channel").await);
+            return None
+        }
+    };
+
+    let handler_lock = join_channel(context, guild_id, connect_to).await;
+
+    if handler_lock.is_none() {
+        check_msg(message.channel_id.say(&context.http, "Not in a voice channel to play in").await);
+    }
+
+    handler_lock
+}
+
+/// Join the given voice channel, or move there if the bot is in another channel of the guild,
+/// and return the call of the guild. The queue of an existing call is kept.
+async fn join_channel(context: &Context, guild_id: GuildId, channel_id: ChannelId) -> Option<Arc<Mutex<Call>>> {
+    let manager = songbird::get(context)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    // checked before joining, as a failed join still sets the channel of the call
+    let was_connected = match manager.get(guild_id) {
+        Some(handler_lock) => handler_lock.lock().await.current_channel().is_some(),
+        None => false
+    };
+
+    let handler_lock = match manager.join(guild_id, channel_id).await {
+        Ok(handler_lock) => handler_lock,
+        Err(e) => {
+            println!("Could not join voice channel of guild {guild_id}: {e:?}");
+            // the manager keeps the call of a failed first join, which would never get its event handlers.
+            // A bot which fails to move keeps its call and queue.
+            if !was_connected {
+                leave_voice_channel(context, guild_id).await;
+            }
+            return None
+        }
+    };
+
+    // the call of a guild is reused until the bot leaves, so its event handlers are only added once
+    if get_set_up_calls(context).await.lock().await.insert(guild_id) {
+        let queue_loop = QueueLoop {
+            call: Arc::downgrade(&handler_lock),
+            guild_id,
+            http_client: get_http_client(context).await,
+            database: get_database(context).await,
+        };
+
+        let mut handler = handler_lock.lock().await;
+        // Attach an event handler to see notifications of all track errors.
+        handler.add_global_event(TrackEvent::Error.into(), TrackErrorNotifier);
+        handler.add_global_event(TrackEvent::End.into(), queue_loop);
+
+        tokio::spawn(leave_when_idle(context.clone(), guild_id, Arc::downgrade(&handler_lock)));
+    }
+
+    Some(handler_lock)
+}
+
+/// Stop the queue and the ambience of the guild and leave its voice channel.
+/// Returns false if the bot was in no voice channel there.
+async fn leave_voice_channel(context: &Context, guild_id: GuildId) -> bool {
+    let manager = songbird::get(context)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    let handler_lock = match manager.get(guild_id) {
+        Some(handler_lock) => handler_lock,
+        None => return false
+    };
+
+    handler_lock.lock().await.queue().stop();
+    get_ambience(context).await.lock().await.clear(guild_id);
+    get_followed_users(context).await.lock().await.remove(&guild_id);
+    get_set_up_calls(context).await.lock().await.remove(&guild_id);
+
+    if let Err(e) = manager.remove(guild_id).await {
+        println!("Could not leave voice channel of guild {guild_id}: {e:?}");
+    }
+
+    true
+}
+
+/// Get how long the bot stays in a voice channel without anything to do, or None if it never leaves on its own
+fn get_idle_time() -> Option<Duration> {
+    let minutes = env::var(IDLE_MINUTES_VARIABLE)
+        .ok()
+        .and_then(|minutes| minutes.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_IDLE_MINUTES);
+
+    match minutes {
+        0 => None,
+        _ => Some(Duration::from_secs(minutes * 60))
+    }
+}
+
+/// Leave the voice channel of the guild once nothing was played or nobody listened for the configured idle time.
+/// Runs until the call is removed.
+async fn leave_when_idle(context: Context, guild_id: GuildId, call: Weak<Mutex<Call>>) {
+    let idle_time = match get_idle_time() {
+        Some(idle_time) => idle_time,
+        None => return
+    };
+
+    let mut idle_since = None;
+
+    loop {
+        tokio::time::sleep(IDLE_CHECK_INTERVAL).await;
+
+        let (channel_id, queue_empty) = match call.upgrade() {
+            Some(call) => {
+                let handler = call.lock().await;
+                match handler.current_channel() {
+                    Some(channel_id) => (ChannelId::new(channel_id.0.get()), handler.queue().is_empty()),
+                    None => {
+                        idle_since = None;
+                        continue
+                    }
+                }
+            },
+            None => return
+        };
+
+        let silent = queue_empty && get_ambience(&context).await.lock().await.tracks(guild_id).is_empty();
+        let idle = silent || !has_listeners(&context, guild_id, channel_id);
+
+        match idle_since {
+            _ if !idle => idle_since = None,
+            None => idle_since = Some(Instant::now()),
+            Some(since) if since.elapsed() >= idle_time => {
+                leave_voice_channel(&context, guild_id).await;
+                return
+            },
+            Some(_) => {}
+        }
+    }
+}
+
+/// Check if any human is in the given voice channel. If the guild is unknown, it is assumed someone is.
+fn has_listeners(context: &Context, guild_id: GuildId, channel_id: ChannelId) -> bool {
+    let guild = match context.cache.guild(guild_id) {
+        Some(guild) => guild,
+        None => return true
+    };
+
+    guild.voice_states
+        .values()
+        .filter(|voice_state| voice_state.channel_id == Some(channel_id))
+        .any(|voice_state| {
+            let is_bot = match &voice_state.member {
+                Some(member) => member.user.bot,
+                None => context.cache.user(voice_state.user_id).map(|user| user.bot).unwrap_or(false)
+            };
+
+            !is_bot
+        })
+}
+
+/// Add the given registered songs to the end of the queue
+async fn enqueue_songs(
+    handler: &mut Call,
+    http_client: &HttpClient,
+    database: &Arc<Mutex<Database>>,
+    songs: &[Song],
+    requester: UserId,
+    settings: &GuildSettings,
+) {
+    if let Err(e) = database.lock().await.mark_songs_used(songs) {
+        println!("Could not mark songs as used: {e}");
+    }
+
+    for song in songs {
+        let handle = queue::enqueue(handler, http_client, database, QueuedSong::new(song, requester), settings).await;
+
+        if song.normalization_gain.is_none() {
+            loudness::analyze_in_background(database.clone(), song.clone(), Some(handle));
+        }
+    }
+}
+
+/// Send one page of a list as an embed, as a message could exceed the length limit of Discord.
+/// The hint tells how to see other pages if there is more than one.
+async fn send_list_page(context: &Context, message: &Message, title: &str, lines: &[String], page: usize, hint: &str) {
+    let pages = lines.len().div_ceil(LIST_PAGE_SIZE);
+    if page > pages {
+        check_msg(message.channel_id.say(&context.http, format!("'{title}' only has {pages} pages")).await);
+        return
+    }
+
+    let mut footer = format!("Page {page} of {pages} | {} entries", lines.len());
+    if pages > 1 {
+        footer += &format!(" | {hint} to see more");
+    }
+
+    let embed = CreateEmbed::new()
+        .title(title)
+        .description(lines.iter().skip((page - 1) * LIST_PAGE_SIZE).take(LIST_PAGE_SIZE).join("\n"))
+        .footer(CreateEmbedFooter::new(footer));
+
+    check_msg(message.channel_id.send_message(&context.http, CreateMessage::new().embed(embed)).await);
+}
+
+/// Get the id of the playlist with the given name in the guild of the message.
+/// If no such playlist exists, the user is told so and None is returned.
+async fn find_playlist(
+    context: &Context,
+    message: &Message,
+    database: &Arc<Mutex<Database>>,
+    name: &str,
+) -> Option<i64> {
+    match database.lock().await.find_playlist(message.guild_id.unwrap().get(), name) {
+        Ok(Some(playlist_id)) => Some(playlist_id),
+        Ok(None) => {
+            check_msg(message.channel_id.say(&context.http, format!("There is no playlist named '{name}'")).await);
+            None
+        },
+        Err(e) => {
+            check_msg(message.channel_id.say(&context.http, format!("Could not load playlist. {e}")).await);
+            None
+        }
+    }
+}
+
 fn get_guild_and_voice_channel(context: &Context, message: &Message) -> (GuildId, Option<ChannelId>) {
     let guild = message.guild(&context.cache).unwrap();
     let channel_id = guild
@@ -1,294 +0,0 @@
-use std::{env, sync::Arc};
-use serenity::client::bridge::voice::ClientVoiceManager;
-use serenity::{client::Context, prelude::Mutex};
-use serenity::{
-    client::{Client, EventHandler},
-    framework::{
-        StandardFramework,
-        standard::{
-            Args, CommandResult,
-            macros::{command, group},
-        },
-    },
-    model::{channel::Message, gateway::Ready, misc::Mentionable},
-    Result as SerenityResult,
-    voice,
-};
-use serenity::prelude::*;
-
-// TODO see https://github.com/serenity-rs/serenity/blob/current/examples/e01_basic_ping_bot/src/main.rs
-
-struct VoiceManager;
-
-impl TypeMapKey for VoiceManager {
-    type Value = Arc<Mutex<ClientVoiceManager>>;
-}
-
-struct Handler;
-
-impl EventHandler for Handler {
-    fn ready(&self, _: Context, ready: Ready) {
-        println!("{} is connected!", ready.user.name);
-    }
-}
-
-#[group]
-#[commands(deafen, join, leave, mute, play, ping, undeafen, unmute)]
-struct General;
-
-fn main() {
-    let token = env::var("DMBOT_TOKEN").expect("Token not set.");
-    let mut client = Client::new(token, Handler).expect("Error creating client.");
-
-    {
-        let mut data = client.data.write();
-        data.insert::<VoiceManager>(Arc::clone(&client.voice_manager));
-    }
-
-    client.with_framework(StandardFramework::new()
-        .configure(|c| c.prefix("§"))
-        .group(&GENERAL_GROUP));
-
-    let _ = client.start().map_err(|why| println!("Client ended: {:?}", why));
-}
-
-#[command]
-fn deafen(ctx: &mut Context, msg: &Message) -> CommandResult {
-    let guild_id = match ctx.cache.read().guild_channel(msg.channel_id) {
-        Some(channel) => channel.read().guild_id,
-        None => {
-            check_msg(msg.channel_id.say(&ctx.http, "Groups and DMs not supported"));
-
-            return Ok(());
-        }
-    };
-
-    let manager_lock = ctx.data.read().get::<VoiceManager>().cloned().unwrap();
-    let mut manager = manager_lock.lock();
-
-    let handler = match manager.get_mut(guild_id) {
-        Some(handler) => handler,
-        None => {
-            check_msg(msg.reply(&ctx, "Not in a voice channel"));
-
-            return Ok(());
-        }
-    };
-
-    if handler.self_deaf {
-        check_msg(msg.channel_id.say(&ctx.http, "Already deafened"));
-    } else {
-        handler.deafen(true);
-
-        check_msg(msg.channel_id.say(&ctx.http, "Deafened"));
-    }
-
-    Ok(())
-}
-
-#[command]
-fn join(ctx: &mut Context, msg: &Message) -> CommandResult {
-    let guild = match msg.guild(&ctx.cache) {
-        Some(guild) => guild,
-        None => {
-            check_msg(msg.channel_id.say(&ctx.http, "Groups and DMs not supported"));
-
-            return Ok(());
-        }
-    };
-
-    let guild_id = guild.read().id;
-
-    let channel_id = guild
-        .read()
-        .voice_states.get(&msg.author.id)
-        .and_then(|voice_state| voice_state.channel_id);
-
-
-    let connect_to = match channel_id {
-        Some(channel) => channel,
-        None => {
-            check_msg(msg.reply(&ctx, "Not in a voice channel"));
-
-            return Ok(());
-        }
-    };
-
-    let manager_lock = ctx.data.read().get::<VoiceManager>().cloned().expect("Expected VoiceManager in ShareMap.");
-    let mut manager = manager_lock.lock();
-
-    if manager.join(guild_id, connect_to).is_some() {
-        check_msg(msg.channel_id.say(&ctx.http, &format!("Joined {}", connect_to.mention())));
-    } else {
-        check_msg(msg.channel_id.say(&ctx.http, "Error joining the channel"));
-    }
-
-    Ok(())
-}
-
-#[command]
-fn leave(ctx: &mut Context, msg: &Message) -> CommandResult {
-    let guild_id = match ctx.cache.read().guild_channel(msg.channel_id) {
-        Some(channel) => channel.read().guild_id,
-        None => {
-            check_msg(msg.channel_id.say(&ctx.http, "Groups and DMs not supported"));
-
-            return Ok(());
-        }
-    };
-
-    let manager_lock = ctx.data.read().get::<VoiceManager>().cloned().expect("Expected VoiceManager in ShareMap.");
-    let mut manager = manager_lock.lock();
-    let has_handler = manager.get(guild_id).is_some();
-
-    if has_handler {
-        manager.remove(guild_id);
-
-        check_msg(msg.channel_id.say(&ctx.http, "Left voice channel"));
-    } else {
-        check_msg(msg.reply(&ctx, "Not in a voice channel"));
-    }
-
-    Ok(())
-}
-
-#[command]
-fn mute(ctx: &mut Context, msg: &Message) -> CommandResult {
-    let guild_id = match ctx.cache.read().guild_channel(msg.channel_id) {
-        Some(channel) => channel.read().guild_id,
-        None => {
-            check_msg(msg.channel_id.say(&ctx.http, "Groups and DMs not supported"));
-
-            return Ok(());
-        }
-    };
-
-    let manager_lock = ctx.data.read().get::<VoiceManager>().cloned().expect("Expected VoiceManager in ShareMap.");
-    let mut manager = manager_lock.lock();
-
-    let handler = match manager.get_mut(guild_id) {
-        Some(handler) => handler,
-        None => {
-            check_msg(msg.reply(&ctx, "Not in a voice channel"));
-
-            return Ok(());
-        }
-    };
-
-    if handler.self_mute {
-        check_msg(msg.channel_id.say(&ctx.http, "Already muted"));
-    } else {
-        handler.mute(true);
-
-        check_msg(msg.channel_id.say(&ctx.http, "Now muted"));
-    }
-
-    Ok(())
-}
-
-#[command]
-fn play(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
-    let url = match args.single::<String>() {
-        Ok(url) => url,
-        Err(_) => {
-            check_msg(msg.channel_id.say(&ctx.http, "Must provide a URL to a video or audio"));
-
-            return Ok(());
-        }
-    };
-
-    if !url.starts_with("http") {
-        check_msg(msg.channel_id.say(&ctx.http, "Must provide a valid URL"));
-
-        return Ok(());
-    }
-
-    let guild_id = match ctx.cache.read().guild_channel(msg.channel_id) {
-        Some(channel) => channel.read().guild_id,
-        None => {
-            check_msg(msg.channel_id.say(&ctx.http, "Error finding channel info"));
-
-            return Ok(());
-        }
-    };
-
-    let manager_lock = ctx.data.read().get::<VoiceManager>().cloned().expect("Expected VoiceManager in ShareMap.");
-    let mut manager = manager_lock.lock();
-
-    if let Some(handler) = manager.get_mut(guild_id) {
-        let source = match voice::ytdl(&url) {
-            Ok(source) => source,
-            Err(why) => {
-                println!("Err starting source: {:?}", why);
-
-                check_msg(msg.channel_id.say(&ctx.http, "Error sourcing ffmpeg"));
-
-                return Ok(());
-            }
-        };
-
-        handler.play(source);
-
-        check_msg(msg.channel_id.say(&ctx.http, "Playing song"));
-    } else {
-        check_msg(msg.channel_id.say(&ctx.http, "Not in a voice channel to play in"));
-    }
-
-    Ok(())
-}
-
-#[command]
-fn undeafen(ctx: &mut Context, msg: &Message) -> CommandResult {
-    let guild_id = match ctx.cache.read().guild_channel(msg.channel_id) {
-        Some(channel) => channel.read().guild_id,
-        None => {
-            check_msg(msg.channel_id.say(&ctx.http, "Error finding channel info"));
-
-            return Ok(());
-        }
-    };
-
-    let manager_lock = ctx.data.read().get::<VoiceManager>().cloned().expect("Expected VoiceManager in ShareMap.");
-    let mut manager = manager_lock.lock();
-
-    if let Some(handler) = manager.get_mut(guild_id) {
-        handler.deafen(false);
-
-        check_msg(msg.channel_id.say(&ctx.http, "Undeafened"));
-    } else {
-        check_msg(msg.channel_id.say(&ctx.http, "Not in a voice channel to undeafen in"));
-    }
-
-    Ok(())
-}
-
-#[command]
-fn unmute(ctx: &mut Context, msg: &Message) -> CommandResult {
-    let guild_id = match ctx.cache.read().guild_channel(msg.channel_id) {
-        Some(channel) => channel.read().guild_id,
-        None => {
-            check_msg(msg.channel_id.say(&ctx.http, "Error finding channel info"));
-
-            return Ok(());
-        }
-    };
-    let manager_lock = ctx.data.read().get::<VoiceManager>().cloned().expect("Expected VoiceManager in ShareMap.");
-    let mut manager = manager_lock.lock();
-
-    if let Some(handler) = manager.get_mut(guild_id) {
-        handler.mute(false);
-
-        check_msg(msg.channel_id.say(&ctx.http, "Unmuted"));
-    } else {
-        check_msg(msg.channel_id.say(&ctx.http, "Not in a voice channel to unmute in"));
-    }
-
-    Ok(())
-}
-
-/// Checks that a message successfully sent; if not, then logs why to stdout.
-fn check_msg(result: SerenityResult<Message>) {
-    if let Err(why) = result {
-        println!("Error sending message: {:?}", why);
-    }
-}
@@ -0,0 +1,197 @@
+use reqwest::Client as HttpClient;
+use serenity::all::{GuildId, UserId};
+use serenity::async_trait;
+use serenity::prelude::{Mutex, TypeMapKey};
+use songbird::tracks::{PlayMode, TrackHandle, TrackQueue};
+use songbird::{Call, Event, EventContext};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use crate::database::{CachedFile, Database, GuildSettings, LoopMode, Song};
+use crate::{cache, fade, loudness, storage};
+
+/// A song in the queue of a guild. Stored in the type map of its track, so the content of the
+/// queue can be inspected later on.
+#[derive(Clone, Debug)]
+pub struct QueuedSong {
+    /// the registered song which was queued, None if it was played by URL
+    pub song_id: Option<i64>,
+    /// URL of a video or path of a local file
+    pub location: String,
+    pub title: String,
+    /// unknown until the metadata of the song was loaded
+    pub duration: Option<Duration>,
+    /// downloaded copy of the song, played instead of the location if it is still valid
+    pub cache: Option<CachedFile>,
+    /// the user who added the song to the queue
+    pub requester: UserId,
+    /// factor on top of the volume of the guild, 1 plays the song unchanged
+    pub gain: f32,
+    /// factor which brings the song to the same loudness as all others, 1 if it is unknown
+    pub normalization: f32,
+}
+
+impl QueuedSong {
+    /// Queue a registered song on behalf of the given user
+    pub fn new(song: &Song, requester: UserId) -> Self {
+        QueuedSong {
+            song_id: Some(song.id),
+            location: song.location(),
+            title: song.title.trim().to_string(),
+            duration: None,
+            cache: song.cache.clone(),
+            requester,
+            gain: (100 + song.volume_offset).max(0) as f32 / 100.0,
+            normalization: song.normalization_gain.map(loudness::to_factor).unwrap_or(1.0),
+        }
+    }
+}
+
+struct QueuedSongKey;
+
+impl TypeMapKey for QueuedSongKey {
+    type Value = QueuedSong;
+}
+
+/// Add the given song to the end of the queue, playing its cached copy if there is a valid one.
+/// An invalid copy is removed from the cache. If its duration is not known yet, it is loaded in the background.
+pub async fn enqueue(
+    handler: &mut Call,
+    http_client: &HttpClient,
+    database: &Arc<Mutex<Database>>,
+    mut song: QueuedSong,
+    settings: &GuildSettings,
+) -> TrackHandle {
+    let location = match (&song.cache, song.song_id) {
+        (Some(cached_file), _) if cache::is_valid(cached_file) => cached_file.path.clone(),
+        (Some(cached_file), Some(song_id)) => {
+            if let Err(e) = cache::remove(&*database.lock().await, song_id, cached_file) {
+                println!("Could not remove invalid cached file of {}: {e}", song.title);
+            }
+            song.cache = None;
+            song.location.clone()
+        },
+        _ => song.location.clone()
+    };
+    let duration_known = song.duration.is_some();
+
+    let handle = handler.enqueue_input(storage::open_input(http_client, &location)).await;
+    let gain = song.gain * song.normalization;
+    handle.typemap().write().await.insert::<QueuedSongKey>(song);
+    fade::attach(&handle, settings.fade, settings.volume as f32 / 100.0, gain).await;
+
+    if settings.loop_mode == LoopMode::Track {
+        let _ = handle.enable_loop();
+    }
+
+    if !duration_known {
+        tokio::spawn(load_duration(handle.clone(), http_client.clone(), location));
+    }
+
+    handle
+}
+
+async fn load_duration(handle: TrackHandle, http_client: HttpClient, location: String) {
+    let duration = match storage::load_duration(&http_client, &location).await {
+        Ok(duration) => duration,
+        Err(e) => {
+            println!("Could not load duration of track {:?}: {e}", handle.uuid());
+            return
+        }
+    };
+
+    if let Some(song) = handle.typemap().write().await.get_mut::<QueuedSongKey>() {
+        song.duration = duration;
+    }
+}
+
+/// Let the tracks of the queue repeat or not, after the loop mode of the guild changed.
+/// Looping the queue is done by [`QueueLoop`] when a track ends.
+pub fn set_loop_mode(queue: &TrackQueue, loop_mode: LoopMode) {
+    for handle in queue.current_queue() {
+        let _ = match loop_mode {
+            LoopMode::Track => handle.enable_loop(),
+            _ => handle.disable_loop()
+        };
+    }
+}
+
+/// Adds tracks which played until their end to the end of the queue again, if the guild loops its queue.
+/// Tracks which were skipped, removed or stopped are not added again.
+pub struct QueueLoop {
+    pub call: Weak<Mutex<Call>>,
+    pub guild_id: GuildId,
+    pub http_client: HttpClient,
+    pub database: Arc<Mutex<Database>>,
+}
+
+#[async_trait]
+impl songbird::events::EventHandler for QueueLoop {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        if let EventContext::Track(track_list) = ctx {
+            for (state, handle) in *track_list {
+                if state.playing != PlayMode::End {
+                    continue
+                }
+
+                let settings = match self.database.lock().await.get_guild_settings(self.guild_id.get()) {
+                    Ok(settings) if settings.loop_mode == LoopMode::Queue => settings,
+                    Ok(_) => continue,
+                    Err(e) => {
+                        println!("Could not load settings of guild {}: {e}", self.guild_id);
+                        continue
+                    }
+                };
+
+                let (song, call) = match (get_queued_song(handle).await, self.call.upgrade()) {
+                    (Some(song), Some(call)) => (song, call),
+                    _ => continue
+                };
+
+                enqueue(&mut *call.lock().await, &self.http_client, &self.database, song, &settings).await;
+            }
+        }
+
+        None
+    }
+}
+
+/// Change the loudness normalization of the song played by the given track, once it was analyzed
+pub async fn set_normalization(handle: &TrackHandle, normalization: f32) {
+    let gain = match handle.typemap().write().await.get_mut::<QueuedSongKey>() {
+        Some(song) => {
+            song.normalization = normalization;
+            song.gain * normalization
+        },
+        None => return
+    };
+
+    fade::set_gain(handle, gain).await;
+}
+
+/// Get the song which is played by the given track
+pub async fn get_queued_song(handle: &TrackHandle) -> Option<QueuedSong> {
+    handle.typemap().read().await.get::<QueuedSongKey>().cloned()
+}
+
+/// Get the songs of the whole queue, starting with the current one
+pub async fn get_queued_songs(handler: &Call) -> Vec<QueuedSong> {
+    let mut songs = vec![];
+
+    for handle in handler.queue().current_queue() {
+        if let Some(song) = get_queued_song(&handle).await {
+            songs.push(song);
+        }
+    }
+
+    songs
+}
+
+/// Format a duration like 3:07 or 1:02:03
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+
+    match seconds / 3600 {
+        0 => format!("{}:{:02}", seconds / 60, seconds % 60),
+        hours => format!("{hours}:{:02}:{:02}", seconds / 60 % 60, seconds % 60)
+    }
+}
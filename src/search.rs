@@ -0,0 +1,163 @@
+use itertools::Itertools;
+
+/// Songs with a lower score than this are not considered to match a query at all
+pub const MIN_SCORE: f64 = 0.5;
+
+/// If the best match is ahead of the second best by at least this much, it is played without asking
+pub const CLEAR_WIN_MARGIN: f64 = 0.15;
+
+/// Matches on tags count less than matches on the title or an alias
+pub const TAG_WEIGHT: f64 = 0.8;
+
+/// Build a full text query for a table using the trigram tokenizer, which matches every text
+/// sharing at least one sequence of three characters with a word of the input.
+/// Words shorter than three characters can not be searched this way and are ignored.
+pub fn trigram_query(input: &str) -> String {
+    tokenize(input)
+        .iter()
+        .flat_map(|token| {
+            let chars = token.chars().collect::<Vec<_>>();
+            chars.windows(3).map(|window| window.iter().collect::<String>()).collect::<Vec<_>>()
+        })
+        .unique()
+        .map(|trigram| format!("\"{trigram}\""))
+        .join(" OR ")
+}
+
+/// Rate how well a title matches a search query, from 0 (unrelated) to 1 (exact match).
+///
+/// Both are split into lowercase alphanumeric tokens and every query token is compared to its best
+/// matching title token, so word order does not matter and small typos like "dargon" still match
+/// "dragon". Only an exact title match (ignoring case and punctuation) reaches a score of 1.
+pub fn score(query: &str, title: &str) -> f64 {
+    let query_tokens = tokenize(query);
+    let title_tokens = tokenize(title);
+
+    if query_tokens.is_empty() || title_tokens.is_empty() {
+        return 0.0
+    }
+
+    if query_tokens == title_tokens {
+        return 1.0
+    }
+
+    let token_score = query_tokens
+        .iter()
+        .map(|query_token| title_tokens
+            .iter()
+            .map(|title_token| token_similarity(query_token, title_token))
+            .fold(0.0, f64::max))
+        .sum::<f64>() / query_tokens.len() as f64;
+
+    // reward queries which appear in the title as they are, like a part of the name
+    let phrase_bonus = match title_tokens.join(" ").contains(&query_tokens.join(" ")) {
+        true => 1.0,
+        false => 0.0
+    };
+
+    token_score * 0.85 + phrase_bonus * 0.1
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_lowercase())
+        .collect()
+}
+
+/// Similarity of two single words between 0 and 1. A prefix (like "drag" for "dragon") counts
+/// almost as much as the whole word, typos are tolerated depending on the length of the word.
+fn token_similarity(query_token: &str, title_token: &str) -> f64 {
+    if query_token == title_token {
+        return 1.0
+    }
+
+    if title_token.starts_with(query_token) {
+        return 0.9
+    }
+
+    let query_length = query_token.chars().count();
+    let allowed_typos = match query_length {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2
+    };
+
+    let distance = edit_distance(query_token, title_token);
+
+    match distance <= allowed_typos {
+        true => 0.8 - 0.1 * distance as f64,
+        false => match title_token.contains(query_token) && query_length >= 3 {
+            true => 0.6,
+            false => 0.0
+        }
+    }
+}
+
+/// Amount of single character insertions, deletions, substitutions or swaps of neighbouring
+/// characters to turn one word into the other
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let substitution_cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + substitution_cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+
+            distances[i][j] = distance;
+        }
+    }
+
+    distances[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typos_still_match() {
+        assert!(score("dargon", "Dragon") >= MIN_SCORE);
+        assert!(score("dargon", "Tavern Music") < MIN_SCORE);
+    }
+
+    #[test]
+    fn exact_title_scores_one() {
+        assert_eq!(score("Dragon Battle", "Dragon Battle"), 1.0);
+        assert_eq!(score("dragon battle!", "Dragon - Battle"), 1.0);
+        assert!(score("dragon", "Dragon Battle") < 1.0);
+    }
+
+    #[test]
+    fn similar_titles_are_no_clear_win() {
+        let battle = score("dragon", "Dragon Battle");
+        let lair = score("dragon", "Dragons Lair");
+
+        assert!(battle > lair);
+        assert!(battle - lair < CLEAR_WIN_MARGIN);
+    }
+
+    #[test]
+    fn short_words_have_no_trigrams() {
+        assert_eq!(trigram_query("ff"), "");
+        assert_eq!(trigram_query("ff battle"), trigram_query("battle"));
+        assert_eq!(trigram_query("Drag"), "\"dra\" OR \"rag\"");
+    }
+}
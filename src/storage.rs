@@ -0,0 +1,75 @@
+use reqwest::Client as HttpClient;
+use serenity::all::Attachment;
+use songbird::input::{Compose, File, Input, YoutubeDl};
+use std::path::PathBuf;
+use std::time::Duration;
+use crate::library;
+
+/// Get the directory with the given name next to the executable, where dmbot stores its files.
+/// The directory is created if it does not exist yet.
+pub fn get_directory(name: &str) -> Result<PathBuf, String> {
+    let mut path = std::env::current_exe().map_err(|e| format!("Could not locate executable: {e}"))?;
+    path.pop();
+    path.push(name);
+
+    std::fs::create_dir_all(&path).map_err(|e| format!("Could not create directory {}: {e}", path.display()))?;
+
+    Ok(path)
+}
+
+/// Download the given message attachment into the directory and return the path of the new file
+pub async fn download_attachment(
+    http_client: &HttpClient,
+    attachment: &Attachment,
+    directory: &str,
+) -> Result<PathBuf, String> {
+    let bytes = http_client
+        .get(&attachment.url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Could not download attachment: {e}"))?
+        .bytes()
+        .await
+        .map_err(|e| format!("Could not download attachment: {e}"))?;
+
+    // the id of the attachment keeps files with the same name apart
+    let file_name = attachment.filename.replace(['/', '\\'], "_");
+    let mut path = get_directory(directory)?;
+    path.push(format!("{}_{file_name}", attachment.id));
+
+    tokio::fs::write(&path, bytes).await.map_err(|e| format!("Could not store attachment: {e}"))?;
+
+    Ok(path)
+}
+
+/// Create an audio input from a location, which is either a URL played through yt-dlp or the path of a local file
+pub fn open_input(http_client: &HttpClient, location: &str) -> Input {
+    match is_url(location) {
+        true => YoutubeDl::new(http_client.clone(), location.to_string()).into(),
+        false => File::new(PathBuf::from(location)).into()
+    }
+}
+
+/// Get the duration of the audio at the given location. URLs are asked through yt-dlp, local files are probed.
+pub async fn load_duration(http_client: &HttpClient, location: &str) -> Result<Option<Duration>, String> {
+    match is_url(location) {
+        true => YoutubeDl::new(http_client.clone(), location.to_string())
+            .aux_metadata()
+            .await
+            .map(|metadata| metadata.duration)
+            .map_err(|e| format!("{e:?}")),
+        false => {
+            let path = PathBuf::from(location);
+            tokio::task::spawn_blocking(move || library::read_file(&path))
+                .await
+                .map_err(|e| format!("{e:?}"))?
+                .map(|file| file.duration)
+        }
+    }
+}
+
+/// Whether the location is a URL to stream from instead of the path of a local file
+pub fn is_url(location: &str) -> bool {
+    location.starts_with("https://") || location.starts_with("http://")
+}
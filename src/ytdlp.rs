@@ -1,3 +1,4 @@
+use std::path::Path;
 use std::process::Command;
 
 pub fn get_video_name(url: &str) -> Result<String, String> {
@@ -7,4 +8,22 @@ pub fn get_video_name(url: &str) -> Result<String, String> {
         .output().unwrap();
 
     Ok(String::from_utf8(output.stdout).unwrap())
-}
\ No newline at end of file
+}
+
+/// Download the audio of the video at the given URL into an m4a file, which symphonia is able to decode
+pub fn download_audio(url: &str, path: &Path) -> Result<(), String> {
+    let output = Command::new("yt-dlp")
+        .arg("--no-playlist")
+        .arg("-f")
+        .arg("bestaudio[ext=m4a]")
+        .arg("-o")
+        .arg(path)
+        .arg(url)
+        .output()
+        .map_err(|e| format!("Could not run yt-dlp: {e}"))?;
+
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}